@@ -23,6 +23,9 @@ fn main() {
                 Err(ParseError::InternalError(err)) => {
                     eprintln!("Internal error, please report the entire error as a bug:\n\n{:?}", err);
                 }
+                Err(err) => {
+                    eprintln!("Failed to parse the data.arc: {:?}", err);
+                }
             }
         } else {
             eprintln!("File does not exist: {}", file_name);
@@ -0,0 +1,252 @@
+use failure::format_err;
+
+use crate::{DATA_ARC_MAGIC, ParseError, hash40};
+use crate::parse::*;
+
+/// Builds a new data.arc in memory from the files added to it.
+///
+/// Only the sections needed to find the files are filled in, every other section is left empty.
+/// The folders of the paths are split on `/` and given a big hash each, linked to the big hash of their parent folder.
+/// Every file is a member of a single big file and the node section is stored uncompressed.
+#[derive(Debug, Default)]
+pub struct ArcBuilder {
+    /// The path and contents of each file, with whether to compress it
+    entries: Vec<(String, Vec<u8>, bool)>,
+}
+
+impl ArcBuilder {
+    pub fn new() -> ArcBuilder {
+        ArcBuilder::default()
+    }
+
+    /// Add a file that `DataArc::get_file(path)` will read `data` from, compressed with zstd when `compress` is true.
+    ///
+    /// Adding the same path twice is not checked, only one of the files would be found by its path.
+    pub fn add_file(&mut self, path: &str, data: &[u8], compress: bool) {
+        self.entries.push((path.to_string(), data.to_vec(), compress));
+    }
+
+    /// Build the data.arc, failing when there is too much data for the fields describing it.
+    pub fn build(self) -> Result<Vec<u8>, ParseError> {
+        let entries = self.entries;
+
+        // The file data, with the FileEntry of each file. Offsets are in units of 4 bytes so files are aligned to 4 bytes.
+        let mut file_data = vec!();
+        let mut file_entries = vec!();
+        for (_, contents, compress) in &entries {
+            file_data.resize((file_data.len() + 3) & !3, 0);
+            let (stored, flags) = if *compress {
+                (zstd::block::compress(contents, 0)?, 0x03000000)
+            } else {
+                (contents.clone(), 0)
+            };
+            file_entries.push(FileEntry {
+                offset: to_u32("offset", file_data.len() as u64 / 4)?,
+                comp_size: to_u32("comp_size", stored.len() as u64)?,
+                decomp_size: to_u32("decomp_size", contents.len() as u64)?,
+                flags,
+            });
+            file_data.extend_from_slice(&stored);
+        }
+        let count = to_u32("tree_count", entries.len() as u64)?;
+
+        // Every folder and the folders containing it get a big hash, in the order they are first used.
+        let mut folders: Vec<&str> = vec!();
+        let mut tree_folders = vec!();
+        for (path, _, _) in &entries {
+            let mut folder = parent(path);
+            tree_folders.push(folder_index(&mut folders, folder));
+            while !folder.is_empty() {
+                folder = parent(folder);
+                folder_index(&mut folders, folder);
+            }
+        }
+
+        let mut body = vec!();
+
+        // big_hashes, every file is in the one big file
+        for folder in &folders {
+            write_pair(&mut body, hash40(folder), 0);
+            write_pair(&mut body, hash40(name(folder)), 0);
+            write_pair(&mut body, hash40(parent(folder)), 0);
+            write_pair(&mut body, 0, 0);
+            write_u32(&mut body, 0); // suboffset_start
+            write_u32(&mut body, count); // num_files
+            body.extend_from_slice(&[0; 0xc]);
+        }
+
+        // big_files
+        write_u64(&mut body, 0); // offset
+        write_u32(&mut body, 0); // decomp_size
+        write_u32(&mut body, 0); // comp_size
+        write_u32(&mut body, 0); // suboffset_index
+        write_u32(&mut body, count); // files
+        write_u32(&mut body, 0);
+
+        // trees, the FileEntry of each tree entry is at its suboffset_index
+        for (i, (path, _, _)) in entries.iter().enumerate() {
+            let file_name = name(path);
+            let ext = file_name.rsplit('.').next().unwrap_or(file_name);
+            write_pair(&mut body, hash40(path), tree_folders[i]);
+            write_pair(&mut body, hash40(ext), 0);
+            write_pair(&mut body, hash40(parent(path)), 0);
+            write_pair(&mut body, hash40(file_name), 0);
+            write_u32(&mut body, i as u32); // suboffset_index
+            write_u32(&mut body, 0); // flags
+        }
+
+        // sub_files1
+        for file_entry in &file_entries {
+            write_u32(&mut body, file_entry.offset);
+            write_u32(&mut body, file_entry.comp_size);
+            write_u32(&mut body, file_entry.decomp_size);
+            write_u32(&mut body, file_entry.flags);
+        }
+
+        // folder_to_big_hash
+        for (i, folder) in folders.iter().enumerate() {
+            write_pair(&mut body, hash40(folder), i as u32);
+        }
+
+        // file_lookup_buckets, the first bucket holds the number of buckets.
+        // Each bucket gives the index and number of its entries in file_lookup, which are sorted by hash.
+        let num_buckets = entries.len().max(1);
+        let mut buckets = vec!(vec!(); num_buckets);
+        for (i, (path, _, _)) in entries.iter().enumerate() {
+            let hash = hash40(path);
+            buckets[(hash % num_buckets as u64) as usize].push((hash, i as u32));
+        }
+        write_u32(&mut body, 0);
+        write_u32(&mut body, num_buckets as u32);
+        let mut index = 0;
+        for bucket in &mut buckets {
+            bucket.sort();
+            write_u32(&mut body, index);
+            write_u32(&mut body, bucket.len() as u32);
+            index += bucket.len() as u32;
+        }
+
+        // file_lookup
+        for (hash, tree_index) in buckets.iter().flatten() {
+            write_pair(&mut body, *hash, *tree_index);
+        }
+
+        let mut node = vec!();
+        write_u32(&mut node, 0); // file_size, filled in once the size is known
+        write_u32(&mut node, folders.len() as u32); // folder_count
+        write_u32(&mut node, 1); // file_count1
+        write_u32(&mut node, count); // tree_count
+        write_u32(&mut node, count); // sub_files1_count
+        write_u32(&mut node, count); // file_lookup_count
+        write_u32(&mut node, 0); // hash_folder_count
+        write_u32(&mut node, 0); // file_information_count
+        write_u32(&mut node, 0); // file_count2
+        write_u32(&mut node, 0); // sub_files2_count
+        write_u32(&mut node, 0); // unk1
+        write_u32(&mut node, 0); // unk2
+        node.extend_from_slice(&[0; 4]); // another_hash_table_size, unk3, unk4
+        write_u32(&mut node, 0); // movie_count
+        write_u32(&mut node, 0); // part1_count
+        write_u32(&mut node, 0); // part2_count
+        write_u32(&mut node, 0); // music_file_count
+        node.extend_from_slice(&body);
+        // A node section smaller than 0x100 bytes would be mistaken for a compressed node section
+        let file_size = node.len().max(0x100);
+        node.resize(file_size, 0);
+        node[..4].copy_from_slice(&to_u32("file_size", file_size as u64)?.to_le_bytes());
+
+        let file_section_offset = (8 + ARC_HEADER_SIZE) as u64;
+        let node_section_offset = (file_section_offset + file_data.len() as u64 + 0xf) & !0xf;
+        let mut arc = vec!();
+        write_u64(&mut arc, DATA_ARC_MAGIC);
+        write_u64(&mut arc, 0); // music_file_section_offset
+        write_u64(&mut arc, file_section_offset);
+        write_u64(&mut arc, 0); // music_section_offset
+        write_u64(&mut arc, node_section_offset);
+        write_u64(&mut arc, 0); // unk_section_offset
+        arc.extend_from_slice(&file_data);
+        arc.resize(node_section_offset as usize, 0);
+        arc.extend_from_slice(&node);
+        Ok(arc)
+    }
+}
+
+/// `value` as a u32, failing when it doesn't fit in the field `name`.
+fn to_u32(name: &str, value: u64) -> Result<u32, ParseError> {
+    if value > u32::MAX as u64 {
+        return Err(format_err!("Failed to build the data.arc: The {} {:#x} does not fit in a u32", name, value).into());
+    }
+    Ok(value as u32)
+}
+
+/// The index of `folder` in `folders`, adding it when it isn't there yet.
+fn folder_index<'a>(folders: &mut Vec<&'a str>, folder: &'a str) -> u32 {
+    match folders.iter().position(|x| *x == folder) {
+        Some(index) => index as u32,
+        None => {
+            folders.push(folder);
+            folders.len() as u32 - 1
+        }
+    }
+}
+
+/// The folder containing `path`, without a trailing `/`.
+fn parent(path: &str) -> &str {
+    path.rfind('/').map_or("", |i| &path[..i])
+}
+
+/// The last component of `path`.
+fn name(path: &str) -> &str {
+    path.rfind('/').map_or(path, |i| &path[i + 1..])
+}
+
+fn write_pair(data: &mut Vec<u8>, hash: u64, meta: u32) {
+    data.extend_from_slice(&hash.to_le_bytes()[..5]);
+    data.extend_from_slice(&meta.to_le_bytes()[..3]);
+}
+
+fn write_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(data: &mut Vec<u8>, value: u64) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DataArc;
+
+    #[test]
+    fn build_then_parse() {
+        let files: &[(&str, &[u8], bool)] = &[
+            ("fighter/mario/model/body/c00/model.numdlb", b"model", true),
+            ("fighter/mario/model/body/c00/model.numshb", &[0xff; 0x1000], true),
+            ("fighter/luigi/param.prc", b"stored", false),
+            ("sound/empty.nus3audio", b"", false),
+            ("root.bin", b"no folder", true),
+        ];
+        let mut builder = ArcBuilder::new();
+        for &(path, data, compress) in files {
+            builder.add_file(path, data, compress);
+        }
+        let mut data_arc = DataArc::parse_owned(builder.build().unwrap()).unwrap();
+
+        assert_eq!(data_arc.tree_entries().len(), files.len());
+        for &(path, data, _) in files {
+            assert_eq!(data_arc.get_file(path).unwrap(), data, "{}", path);
+        }
+        assert!(data_arc.get_file("fighter/mario/missing.prc").is_err());
+        data_arc.self_check().unwrap();
+        data_arc.validate_indices().unwrap();
+        data_arc.check_alignment().unwrap();
+    }
+
+    #[test]
+    fn build_then_parse_empty() {
+        let mut data_arc = DataArc::parse_owned(ArcBuilder::new().build().unwrap()).unwrap();
+        assert!(data_arc.tree_entries().is_empty());
+        assert!(matches!(data_arc.get_file("a.prc"), Err(crate::GetFileError::FileNotFound)));
+    }
+}
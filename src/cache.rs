@@ -0,0 +1,221 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use failure::format_err;
+use scroll::{Pread, LE};
+
+use crate::{DataArc, NodeFormat, NodeSection, ParseError, ParseOptions, check_node_limit, read_arc_header};
+use crate::parse::*;
+
+/// Identifies a file written by `DataArc::save_cache`, followed by the version of the format.
+const CACHE_MAGIC: &[u8; 8] = b"ARCCACHE";
+const CACHE_VERSION: u32 = 1;
+
+impl DataArc {
+    /// Write the metadata of the data.arc to `out`, so it can be loaded with `DataArc::load_cache` instead of parsing the data.arc again.
+    ///
+    /// The cache holds the headers and the decompressed node section, so it is about the size of the node section once decompressed.
+    /// The labels are not included.
+    pub fn save_cache<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(CACHE_MAGIC)?;
+        out.write_u32::<LittleEndian>(CACHE_VERSION)?;
+        out.write_u64::<LittleEndian>(self.base_offset)?;
+        out.write_u64::<LittleEndian>(self.file_len)?;
+        write_arc_header(&mut out, &self.header)?;
+
+        out.write_u64::<LittleEndian>(self.node_section.offset)?;
+        out.write_u64::<LittleEndian>(self.node_section.size)?;
+        match self.node_section.format {
+            NodeFormat::Compressed { comp_size, decomp_size } => {
+                out.write_u8(1)?;
+                out.write_u32::<LittleEndian>(comp_size)?;
+                out.write_u32::<LittleEndian>(decomp_size)?;
+            }
+            NodeFormat::Uncompressed { size } => {
+                out.write_u8(0)?;
+                out.write_u32::<LittleEndian>(size)?;
+                out.write_u32::<LittleEndian>(0)?;
+            }
+        }
+
+        out.write_u64::<LittleEndian>((NODE_HEADER_SIZE + self.buffer.len()) as u64)?;
+        write_node_header(&mut out, &self.node_header)?;
+        out.write_all(&self.buffer)
+    }
+
+    /// Load the metadata written by `DataArc::save_cache` and read file data from `file`, configured by `options`.
+    ///
+    /// The magic offset is taken from the cache, `ParseOptions::magic_offset` is ignored.
+    /// Fails with `ParseError::Mismatch` when the size of `file`, its `ArcHeader` or the header of its node section as stored
+    /// differ from when the cache was saved, i.e. the cache is stale.
+    /// The header of a node section stored uncompressed is its `NodeHeader`, holding the count of every section,
+    /// and the header of a compressed one gives the sizes of the zstd data.
+    /// A change to the node section that keeps the size of the file and these headers the same is not detected.
+    pub fn load_cache<R: Read>(mut cache: R, mut file: File, options: ParseOptions) -> Result<DataArc, ParseError> {
+        let mut magic = [0; 8];
+        cache.read_exact(&mut magic)?;
+        let version = cache.read_u32::<LittleEndian>()?;
+        if &magic != CACHE_MAGIC || version != CACHE_VERSION {
+            return Err(format_err!("Failed to load the cache: Not a cache of version {}", CACHE_VERSION).into());
+        }
+        let base_offset = cache.read_u64::<LittleEndian>()?;
+        let file_len = cache.read_u64::<LittleEndian>()?;
+        let header = read_cached_arc_header(&mut cache)?;
+
+        let offset = cache.read_u64::<LittleEndian>()?;
+        let size = cache.read_u64::<LittleEndian>()?;
+        let compressed = cache.read_u8()? != 0;
+        let first_size = cache.read_u32::<LittleEndian>()?;
+        let second_size = cache.read_u32::<LittleEndian>()?;
+        let format = if compressed {
+            NodeFormat::Compressed { comp_size: first_size, decomp_size: second_size }
+        } else {
+            NodeFormat::Uncompressed { size: first_size }
+        };
+
+        let node_len = cache.read_u64::<LittleEndian>()?;
+        // The cache may be corrupt, so check the size the same as when parsing before reading the node section
+        let remaining = file_len.saturating_sub(base_offset.saturating_add(header.node_section_offset));
+        if node_len < NODE_HEADER_SIZE as u64 || (!compressed && node_len > remaining) {
+            return Err(ParseError::InvalidNodeSize { size: node_len, remaining });
+        }
+        check_node_limit(node_len, options.max_node_size)?;
+        let mut node = vec!();
+        cache.take(node_len).read_to_end(&mut node)?;
+        if node.len() as u64 != node_len {
+            return Err(format_err!("Failed to load the cache: The node section is truncated").into());
+        }
+
+        let mut differences = vec!();
+        let current_len = file.seek(SeekFrom::End(0))?;
+        if current_len != file_len {
+            differences.push(format!("The data.arc was {:#x} bytes but is now {:#x} bytes", file_len, current_len));
+        }
+        file.seek(SeekFrom::Start(base_offset))?;
+        let current_header = read_arc_header(&mut file, options.expected_magic)?;
+        let mut cached_bytes = vec!();
+        let mut current_bytes = vec!();
+        write_arc_header(&mut cached_bytes, &header)?;
+        write_arc_header(&mut current_bytes, &current_header)?;
+        if cached_bytes != current_bytes {
+            differences.push(format!("The ArcHeader was {:x?} but is now {:x?}", header, current_header));
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        if compressed {
+            let mut stored = [0; COMPRESSED_NODE_HEADER_SIZE];
+            file.read_exact(&mut stored)?;
+            let stored: CompressedNodeHeader = stored.pread_with(0, LE)?;
+            let stored_size = stored.data_start as u64 + stored.zstd_comp_size as u64;
+            if (stored_size, stored.zstd_comp_size, stored.decomp_size) != (size, first_size, second_size) {
+                differences.push(format!("The compressed node section was {:#x} bytes decompressing to {:#x} bytes but is now {:#x} bytes decompressing to {:#x} bytes",
+                    size, second_size, stored_size, stored.decomp_size));
+            }
+        } else {
+            let mut stored = [0; NODE_HEADER_SIZE];
+            file.read_exact(&mut stored)?;
+            if stored[..] != node[..NODE_HEADER_SIZE] {
+                let cached_header: NodeHeader = node.pread_with(0, LE)?;
+                let stored_header: NodeHeader = stored.pread_with(0, LE)?;
+                differences.push(format!("The NodeHeader was {:x?} but is now {:x?}", cached_header, stored_header));
+            }
+        }
+        if !differences.is_empty() {
+            return Err(ParseError::Mismatch(differences));
+        }
+
+        let mut data_arc = DataArc::decode_node_section(header, file_len, base_offset, options.max_entries_per_section, node, None)?;
+        data_arc.node_section = NodeSection { offset, size, compressed, format };
+        data_arc.magic = options.expected_magic;
+        #[cfg(feature = "digest")]
+        data_arc.raw_headers.splice(0..0, cached_bytes);
+        data_arc.apply_options(&options, true)?;
+        data_arc.with_backend(file, options)
+    }
+}
+
+fn write_arc_header<W: Write>(out: &mut W, header: &ArcHeader) -> io::Result<()> {
+    out.write_u64::<LittleEndian>(header.music_file_section_offset)?;
+    out.write_u64::<LittleEndian>(header.file_section_offset)?;
+    out.write_u64::<LittleEndian>(header.music_section_offset)?;
+    out.write_u64::<LittleEndian>(header.node_section_offset)?;
+    out.write_u64::<LittleEndian>(header.unk_section_offset)
+}
+
+fn read_cached_arc_header<R: Read>(reader: &mut R) -> io::Result<ArcHeader> {
+    Ok(ArcHeader {
+        music_file_section_offset: reader.read_u64::<LittleEndian>()?,
+        file_section_offset: reader.read_u64::<LittleEndian>()?,
+        music_section_offset: reader.read_u64::<LittleEndian>()?,
+        node_section_offset: reader.read_u64::<LittleEndian>()?,
+        unk_section_offset: reader.read_u64::<LittleEndian>()?,
+    })
+}
+
+/// Write `header` as it is stored at the start of the node section.
+fn write_node_header<W: Write>(out: &mut W, header: &NodeHeader) -> io::Result<()> {
+    for &value in &[
+        header.file_size, header.folder_count, header.file_count1, header.tree_count,
+        header.sub_files1_count, header.file_lookup_count, header.hash_folder_count, header.file_information_count,
+        header.file_count2, header.sub_files2_count, header.unk1, header.unk2,
+    ] {
+        out.write_u32::<LittleEndian>(value)?;
+    }
+    out.write_u8(header.another_hash_table_size)?;
+    out.write_u8(header.unk3)?;
+    out.write_u16::<LittleEndian>(header.unk4)?;
+    for &value in &[header.movie_count, header.part1_count, header.part2_count, header.music_file_count] {
+        out.write_u32::<LittleEndian>(value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{ArcBuilder, DataArc, ParseError, ParseOptions};
+
+    /// Write a data.arc with an uncompressed node section to a file named `name` in the temporary directory.
+    fn write_arc(name: &str) -> (PathBuf, Vec<u8>) {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/file.bin", b"contents", false);
+        let data = builder.build().unwrap();
+        let path = std::env::temp_dir().join(format!("ultimate_data_arc_cache_{}_{}", name, std::process::id()));
+        fs::write(&path, &data).unwrap();
+        (path, data)
+    }
+
+    #[test]
+    fn changed_node_header_is_stale() {
+        let (path, mut data) = write_arc("stale");
+        let mut cache = vec!();
+        DataArc::new(File::open(&path).unwrap()).unwrap().save_cache(&mut cache).unwrap();
+        assert!(DataArc::load_cache(&cache[..], File::open(&path).unwrap(), ParseOptions::new()).is_ok());
+
+        // Change the unk1 field of the NodeHeader, which keeps the size of the file and the ArcHeader the same
+        let node = LittleEndian::read_u64(&data[0x20..]) as usize;
+        data[node + 0x28] ^= 1;
+        fs::write(&path, &data).unwrap();
+        let result = DataArc::load_cache(&cache[..], File::open(&path).unwrap(), ParseOptions::new());
+        fs::remove_file(&path).unwrap();
+        match result {
+            Err(ParseError::Mismatch(differences)) => assert!(differences[0].starts_with("The NodeHeader was"), "{:?}", differences),
+            Err(error) => panic!("expected Mismatch, got {:?}", error),
+            Ok(_) => panic!("expected Mismatch"),
+        }
+    }
+
+    #[test]
+    fn cached_node_size_is_limited() {
+        let (path, _) = write_arc("limit");
+        let mut cache = vec!();
+        DataArc::new(File::open(&path).unwrap()).unwrap().save_cache(&mut cache).unwrap();
+        let result = DataArc::load_cache(&cache[..], File::open(&path).unwrap(), ParseOptions::new().max_node_size(0x80));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result.err(), Some(ParseError::NodeTooLarge { max_size: 0x80, .. })));
+    }
+}
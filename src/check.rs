@@ -0,0 +1,429 @@
+use std::io::BufReader;
+
+use failure::format_err;
+
+use scroll::{Pread, LE};
+
+use crate::{Backend, DataArc, GetFileError, ParseError, ParseOptions, check_big_hash_alignment};
+use crate::parse::*;
+
+/// An index in an entry of one section pointing past the end of another section, returned by `DataArc::validate_indices`.
+#[derive(Debug, Clone)]
+pub struct IndexError {
+    /// The section containing the entry, e.g. `trees`
+    pub section: &'static str,
+    /// Index of the entry in `section`
+    pub entry: usize,
+    /// The field of the entry holding the index, e.g. `suboffset_index`
+    pub field: &'static str,
+    /// The out of range index, or the last index used when the field is the start of a range
+    pub index: u64,
+    /// The section the index points into
+    pub target: &'static str,
+    /// Number of entries in `target`
+    pub target_len: usize,
+}
+
+/// Whether the node section has the layout this crate parses, returned by `DataArc::version` and in `ParseError::UnsupportedVersion`.
+///
+/// No field of the data.arc is known to hold its version, so the layout is recognised by checking the sections line up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArcVersion {
+    /// The layout described by `NodeHeader`, the only layout this crate knows
+    Known,
+    /// The layout of data.arcs from before version 3.0.0 of the game, whose node header is smaller.
+    /// It is assumed to end before `NodeHeader::movie_count`, without the bulkfile and music sections counted by the fields after it,
+    /// so it is recognised by the sections only lining up with a node header of 0x34 bytes.
+    /// This has not been verified against a data.arc from before 3.0.0.
+    /// Only returned in `ParseError::UnsupportedVersion`, as parsing always fails.
+    Pre300,
+    /// The sections are misaligned or followed by data that isn't zero, see `DataArc::check_alignment` and `DataArc::trailing_node_bytes`.
+    /// The node section probably has the layout of a different version of the game, so the entries of its sections can't be trusted.
+    Unknown,
+}
+
+/// The size of the node header in the pre-3.0.0 layout, see `ArcVersion::Pre300`.
+const PRE_300_NODE_HEADER_SIZE: usize = 0x34;
+
+/// Whether the sections of `node`, an entire node section starting with its header, only line up when it has the pre-3.0.0 layout.
+pub(crate) fn is_pre_300_layout(node: &[u8]) -> bool {
+    let mut node_header: NodeHeader = match node.pread_with(0, LE) {
+        Ok(node_header) => node_header,
+        Err(_) => return false,
+    };
+    if node_header.folder_count == 0 || big_hashes_line_up(&node[NODE_HEADER_SIZE..], &node_header) {
+        return false;
+    }
+    // These counts would be read from the start of the first section, which has moved up into where they are stored
+    node_header.movie_count = 0;
+    node_header.part1_count = 0;
+    node_header.part2_count = 0;
+    node_header.music_file_count = 0;
+    big_hashes_line_up(&node[PRE_300_NODE_HEADER_SIZE..], &node_header)
+}
+
+/// Whether the first `folder_to_big_hash` entry of `buffer`, the node section after a header of `node_header`, points at a `big_hashes` entry with the same hash.
+fn big_hashes_line_up(buffer: &[u8], node_header: &NodeHeader) -> bool {
+    let offsets = node_header.section_offsets();
+    offsets.folder_to_big_hash.saturating_add(ENTRY_PAIR_SIZE) <= buffer.len()
+        && check_big_hash_alignment(buffer, offsets.big_hashes, offsets.folder_to_big_hash, node_header.folder_count).is_none()
+}
+
+impl DataArc {
+    /// Guess whether the node section has the layout this crate parses, see `ArcVersion`.
+    ///
+    /// Parsing fails with `ParseError::UnsupportedVersion` when this isn't `ArcVersion::Known`, unless `ParseOptions::check_version` is turned off.
+    pub fn version(&self) -> ArcVersion {
+        if self.check_alignment().is_ok() && self.trailing_node_bytes().iter().all(|&x| x == 0) {
+            ArcVersion::Known
+        } else {
+            ArcVersion::Unknown
+        }
+    }
+
+    /// Check the invariants of the data.arc that the parser relies on, returning a description of every one that is violated.
+    ///
+    /// This checks that:
+    /// *   The sections of the node section are in order and fit within it.
+    /// *   Every tree entry refers to a `FileEntry`, big hash and big file that exist.
+    /// *   The data of every file fits within the data.arc.
+    /// *   Every section of the `ArcHeader` and the data of every music file starts within the data.arc.
+    pub fn self_check(&self) -> Result<(), Vec<String>> {
+        let mut violations = vec!();
+
+        for &(name, start, _) in &self.top_level_sections() {
+            if start > self.file_len {
+                violations.push(format!("The {} at {:#x} starts past the end of the data.arc of size {:#x}", name, start, self.file_len));
+            }
+        }
+        for (i, music_file) in self.music_files().iter().enumerate() {
+            let start = self.base_offset.saturating_add(music_file.offset);
+            if start.saturating_add(music_file.size) > self.file_len {
+                violations.push(format!("Music file {} at {:#x} of size {:#x} runs past the end of the data.arc of size {:#x}", i, start, music_file.size, self.file_len));
+            }
+        }
+
+        let sections = self.section_starts();
+        for pair in sections.windows(2) {
+            let (name, start) = pair[0];
+            let (next_name, next_start) = pair[1];
+            if start > next_start {
+                violations.push(format!("The {} section at {:#x} starts after the {} section at {:#x}", name, start, next_name, next_start));
+            }
+        }
+        for &(name, start) in &sections {
+            if start > self.buffer.len() {
+                violations.push(format!("The {} section at {:#x} starts past the end of the node section of size {:#x}", name, start, self.buffer.len()));
+            }
+        }
+
+        if !violations.is_empty() {
+            // The entries can't be safely read from sections that are out of place
+            return Err(violations);
+        }
+
+        if self.tree_entries().len() != self.node_header.tree_count as usize {
+            violations.push(format!("{} tree entries were decoded but the node header counts {}", self.tree_entries().len(), self.node_header.tree_count));
+        }
+        let file_entry_count = self.node_header.sub_files1_count as usize + self.node_header.sub_files2_count as usize;
+        if self.file_entries().len() != file_entry_count {
+            violations.push(format!("{} file entries were decoded but the node header counts {}", self.file_entries().len(), file_entry_count));
+        }
+
+        let big_file_count = self.node_header.file_count1 as usize + self.node_header.file_count2 as usize;
+        for (i, tree) in self.tree_entries().iter().enumerate() {
+            match self.resolve_file_entry(tree) {
+                Ok(_) => {}
+                Err(GetFileError::UnsupportedRedirect { .. }) => continue,
+                Err(_) => {
+                    violations.push(format!("Tree entry {} refers to a FileEntry out of range of the {} in sub_files1", i, self.node_header.sub_files1_count));
+                    continue;
+                }
+            }
+            if tree.path.meta >= self.node_header.folder_count {
+                violations.push(format!("Tree entry {} refers to big hash {} out of range of the {} in big_hashes", i, tree.path.meta, self.node_header.folder_count));
+                continue;
+            }
+            let big_hash = match self.big_hash_entry(tree.path.meta as usize) {
+                Some(big_hash) => big_hash,
+                None => {
+                    violations.push(format!("Tree entry {} refers to big hash {} past the end of the node section", i, tree.path.meta));
+                    continue;
+                }
+            };
+            if big_hash.path.meta as usize >= big_file_count {
+                violations.push(format!("Tree entry {} refers to big file {} out of range of the {} in big_files", i, big_hash.path.meta, big_file_count));
+                continue;
+            }
+            if let Err(err) = self.locate_tree(tree) {
+                violations.push(format!("Tree entry {} can't be located: {:?}", i, err));
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Check that the sections of the node section are where their entries are, returning a description of every section that is not.
+    ///
+    /// The node header only gives the number of entries in each section and each section is assumed to start where the previous one ends,
+    /// so a wrong count or sections stored in a different order misalign the sections after it. This is detected by checking that:
+    /// *   The first `folder_to_big_hash` entry has the same hash as the `big_hashes` entry it points at.
+    /// *   The `file_lookup_buckets` cover the `file_lookup_count` entries of `file_lookup` in order.
+    /// *   The first `file_lookup` entry has the same hash as the tree entry it points at.
+    pub fn check_alignment(&self) -> Result<(), Vec<String>> {
+        let mut violations = vec!();
+
+        let folder_count = self.node_header.folder_count;
+        if let Some(error) = check_big_hash_alignment(&self.buffer, self.big_hashes, self.folder_to_big_hash, folder_count) {
+            violations.push(error.to_string());
+        }
+
+        let num_buckets = self.first_hash_bucket.num_entries as usize;
+        let mut expected_index = 0;
+        let mut buckets_in_order = true;
+        for i in 1..=num_buckets {
+            let start = self.file_lookup_buckets + HASH_BUCKET_SIZE * i;
+            let bucket: HashBucket = match self.buffer.get(start..start + HASH_BUCKET_SIZE) {
+                Some(data) => data.pread_with(0, LE).unwrap_or_default(),
+                None => break,
+            };
+            if bucket.index != expected_index {
+                violations.push(format!(
+                    "file_lookup_buckets entry {} starts at file_lookup entry {} instead of {}, the file_lookup_buckets section may be misaligned",
+                    i, bucket.index, expected_index
+                ));
+                buckets_in_order = false;
+                break;
+            }
+            expected_index = bucket.index.saturating_add(bucket.num_entries);
+        }
+        if buckets_in_order && num_buckets > 0 && expected_index != self.node_header.file_lookup_count {
+            violations.push(format!(
+                "file_lookup_buckets cover {} file_lookup entries but the node header counts {}, the file_lookup_buckets section may be misaligned",
+                expected_index, self.node_header.file_lookup_count
+            ));
+        }
+
+        if let Some(data) = self.buffer.get(self.file_lookup..self.file_lookup + ENTRY_PAIR_SIZE) {
+            if self.node_header.file_lookup_count > 0 {
+                let pair = read_pair(data);
+                match self.tree_entries().get(pair.meta as usize) {
+                    Some(tree) if tree.path.hash == pair.hash => {}
+                    _ => violations.push(format!(
+                        "The first file_lookup entry {:#x} does not match tree entry {}, the trees or file_lookup section may be misaligned",
+                        pair.hash, pair.meta
+                    )),
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Check that every index from an entry of one section into another section is within that section.
+    ///
+    /// This checks:
+    /// *   The `suboffset_index` (or `ext.meta`, depending on its flags) of each tree entry is within `sub_files1`,
+    ///     the only section `DataArc::file_entry_for` finds file entries in.
+    /// *   The `path.meta` of each tree entry is within `big_hashes`.
+    /// *   The `path.meta` of each big hash is within `big_files` and its `suboffset_start` is within the file entries.
+    /// *   The `files` members starting at the `suboffset_index` of each big file are within the file entries.
+    ///
+    /// The file entries are the `sub_files1` section followed by the `sub_files2` section.
+    /// It is assumed from its name, but not verified, that `suboffset_start` indexes the file entries.
+    /// Redirects are skipped, as where they point is not known.
+    pub fn validate_indices(&self) -> Result<(), Vec<IndexError>> {
+        let mut errors = vec!();
+        let file_entry_count = self.file_entries().len();
+        let sub_files1_count = file_entry_count.min(self.node_header.sub_files1_count as usize);
+        let folder_count = self.node_header.folder_count as usize;
+        let big_files: Vec<BigFileEntry> = self.big_files().collect();
+        let mut check = |section, entry, field, index: u64, target, target_len: usize| {
+            if index >= target_len as u64 {
+                errors.push(IndexError { section, entry, field, index, target, target_len });
+            }
+        };
+
+        for (i, tree) in self.tree_entries().iter().enumerate() {
+            if tree.redirect() {
+                continue;
+            }
+            if tree.suboffset_index() {
+                check("trees", i, "suboffset_index", tree.suboffset_index as u64, "sub_files1", sub_files1_count);
+            } else {
+                check("trees", i, "ext.meta", tree.ext.meta as u64, "sub_files1", sub_files1_count);
+            }
+            check("trees", i, "path.meta", tree.path.meta as u64, "big_hashes", folder_count);
+        }
+
+        for i in 0..folder_count {
+            let start = self.big_hashes + BIG_HASH_ENTRY_SIZE * i;
+            let big_hash = match self.buffer.get(start..start + BIG_HASH_ENTRY_SIZE) {
+                Some(data) => read_big_hash_entry(data),
+                None => break,
+            };
+            check("big_hashes", i, "path.meta", big_hash.path.meta as u64, "big_files", big_files.len());
+            if big_hash.num_files > 0 {
+                check("big_hashes", i, "suboffset_start", big_hash.suboffset_start as u64, "sub_files", file_entry_count);
+            }
+        }
+
+        for (i, big_file) in big_files.iter().enumerate() {
+            if big_file.files > 0 {
+                let last = big_file.suboffset_index as u64 + big_file.files as u64 - 1;
+                check("big_files", i, "suboffset_index", last, "sub_files", file_entry_count);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parse the data.arc again from the file, memory map, bytes or reader it is read from, e.g. to check that patching it in place didn't corrupt it.
+    ///
+    /// Fails with `ParseError::Mismatch` when the section offsets, the size of the node section or the counts in the node header
+    /// differ from when it was first parsed, or when the parsed data.arc fails `DataArc::self_check`.
+    /// The data.arc is parsed again with the limits of `ParseOptions::new`, so a count in the node header that is over them fails with `ParseError::SectionOverrun`.
+    /// The in memory state of this `DataArc` is not changed.
+    pub fn reparse_self(&mut self) -> Result<(), ParseError> {
+        let options = ParseOptions::new()
+            .magic_offset(self.base_offset)
+            .expected_magic(self.magic)
+            .lazy(true);
+        let reparsed = match self.backend.as_mut() {
+            Some(Backend::File(file)) => DataArc::parse_reader(&mut BufReader::new(file), &options, None)?,
+            #[cfg(feature = "mmap")]
+            Some(Backend::Mmap(mmap)) => DataArc::parse_reader(&mut std::io::Cursor::new(&mmap[..]), &options, None)?,
+            Some(Backend::Memory(data)) => DataArc::parse_reader(&mut std::io::Cursor::new(&data[..]), &options, None)?,
+            Some(Backend::Reader(reader)) => DataArc::parse_reader(&mut BufReader::new(reader), &options, None)?,
+            None => return Err(format_err!("Failed to reparse the data.arc: It was parsed with DataArc::new_metadata_only").into()),
+        };
+
+        let mut differences = vec!();
+        let offsets = [
+            ("music_file_section_offset", self.header.music_file_section_offset, reparsed.header.music_file_section_offset),
+            ("file_section_offset", self.header.file_section_offset, reparsed.header.file_section_offset),
+            ("music_section_offset", self.header.music_section_offset, reparsed.header.music_section_offset),
+            ("node_section_offset", self.header.node_section_offset, reparsed.header.node_section_offset),
+            ("unk_section_offset", self.header.unk_section_offset, reparsed.header.unk_section_offset),
+            ("node section size", self.buffer.len() as u64, reparsed.buffer.len() as u64),
+        ];
+        for (name, before, after) in offsets {
+            if before != after {
+                differences.push(format!("The {} was {:#x} but is now {:#x}", name, before, after));
+            }
+        }
+        for (&(name, before), &(_, after)) in self.node_header.counts().iter().zip(reparsed.node_header.counts().iter()) {
+            if before != after {
+                differences.push(format!("The {} was {} but is now {}", name, before, after));
+            }
+        }
+
+        if differences.is_empty() {
+            if let Err(violations) = reparsed.self_check() {
+                differences = violations;
+            }
+        }
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseError::Mismatch(differences))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{ArcBuilder, ArcVersion, Backend, DataArc, ParseError, ParseOptions};
+
+    /// A data.arc whose last FileEntry is counted in `sub_files2` instead of `sub_files1`, so the layout is unchanged.
+    fn last_file_in_sub_files2() -> DataArc {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/first.bin", b"first", false);
+        builder.add_file("a/second.bin", b"second", false);
+        let mut data = builder.build().unwrap();
+        let node = LittleEndian::read_u64(&data[0x20..]) as usize;
+        LittleEndian::write_u32(&mut data[node + 0x10..], 1); // sub_files1_count
+        LittleEndian::write_u32(&mut data[node + 0x24..], 1); // sub_files2_count
+        DataArc::parse_owned(data).unwrap()
+    }
+
+    #[test]
+    fn tree_index_into_sub_files2() {
+        let mut data_arc = last_file_in_sub_files2();
+        let errors = data_arc.validate_indices().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].entry, errors[0].target, errors[0].target_len), (1, "sub_files1", 1));
+        assert_eq!(data_arc.self_check().unwrap_err().len(), 1);
+        assert!(data_arc.get_file("a/second.bin").is_err());
+        assert_eq!(data_arc.get_file("a/first.bin").unwrap(), b"first");
+    }
+
+    /// A data.arc with several files whose node section is larger than 0x100 bytes, so it stays uncompressed once 0x10 bytes are removed.
+    fn build_arc() -> Vec<u8> {
+        let mut builder = ArcBuilder::new();
+        for i in 0..4 {
+            builder.add_file(&format!("folder{}/file{}.bin", i, i), b"contents", false);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn pre_300_layout_is_unsupported() {
+        let data = build_arc();
+        let node = LittleEndian::read_u64(&data[0x20..]) as usize;
+        // Remove the counts after unk4 from the node header, the counts of sections ArcBuilder doesn't write
+        let mut pre_300 = data[..node + 0x34].to_vec();
+        pre_300.extend_from_slice(&data[node + 0x44..]);
+        let size = (pre_300.len() - node) as u32;
+        assert!(size >= 0x100);
+        LittleEndian::write_u32(&mut pre_300[node..], size);
+
+        let error = DataArc::parse_owned(pre_300.clone()).err().unwrap();
+        assert!(matches!(error, ParseError::UnsupportedVersion { detected: ArcVersion::Pre300 }));
+        assert!(DataArc::parse_owned(data).is_ok());
+    }
+
+    #[test]
+    fn misaligned_layout_is_unsupported() {
+        let mut data = build_arc();
+        let node = LittleEndian::read_u64(&data[0x20..]) as usize;
+        data[node + 0x30] = 1; // another_hash_table_size, moving every section after it
+        data.extend_from_slice(&[0; 0xc]);
+        let size = (data.len() - node) as u32;
+        LittleEndian::write_u32(&mut data[node..], size);
+
+        let error = DataArc::parse_owned(data.clone()).err().unwrap();
+        assert!(matches!(error, ParseError::UnsupportedVersion { detected: ArcVersion::Unknown }));
+        let data_arc = DataArc::parse_owned_with_options(data, ParseOptions::new().check_version(false)).unwrap();
+        assert_eq!(data_arc.version(), ArcVersion::Unknown);
+    }
+
+    #[test]
+    fn reparse_self_limits_counts() {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/first.bin", b"first", false);
+        let mut data_arc = DataArc::parse_owned(builder.build().unwrap()).unwrap();
+        data_arc.reparse_self().unwrap();
+
+        if let Some(Backend::Memory(data)) = data_arc.backend.as_mut() {
+            let node = LittleEndian::read_u64(&data[0x20..]) as usize;
+            LittleEndian::write_u32(&mut data[node + 0xc..], u32::MAX); // tree_count
+        }
+        assert!(matches!(data_arc.reparse_self(), Err(ParseError::SectionOverrun { count_name: "tree_count", .. })));
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::{self, Write};
+
+use crate::DataArc;
+
+impl DataArc {
+    /// Write a CSV table with a header row and a row for each tree entry, in the order they are stored in the `trees` section.
+    ///
+    /// The columns are `path_hash`, `name`, `ext`, `folder_hash`, `decomp_size`, `comp_size`, `offset` and `flags`:
+    /// *   `name` is the path from the labels and is empty when there is no label.
+    /// *   `ext` is the extension from the labels, falling back to its hash formatted as `0x{hash:x}`.
+    /// *   `offset` is the absolute offset of the data in the data.arc.
+    /// *   The sizes, `offset` and the `flags` of the `FileEntry` are empty when the file can't be located.
+    pub fn to_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "path_hash,name,ext,folder_hash,decomp_size,comp_size,offset,flags")?;
+        for tree in self.tree_entries() {
+            let name = self.labels.as_ref().and_then(|x| x.resolve(tree.path.hash)).unwrap_or("");
+            let ext = match &self.labels {
+                Some(labels) => labels.resolve_or_hex(tree.ext.hash),
+                None => format!("0x{:x}", tree.ext.hash),
+            };
+            let file = match self.locate_tree(tree).ok() {
+                Some(location) => format!(
+                    "{},{},{:#x},{:#x}",
+                    location.sub_file.decomp_size, location.sub_file.comp_size, location.offset, location.sub_file.flags
+                ),
+                None => String::from(",,,"),
+            };
+            writeln!(out, "{:#x},{},{},{:#x},{}", tree.path.hash, quote(name), quote(&ext), tree.folder.hash, file)?;
+        }
+        Ok(())
+    }
+
+    /// Write a CSV table with a header row and a row for each field whose meaning is not known yet,
+    /// to help look for patterns in them.
+    ///
+    /// The columns are `section`, `index`, `field` and `value`, where `value` is formatted as `0x{value:x}`.
+    /// Rows are written for the `unk` fields of the `NodeHeader`, then each entry of the `big_hashes` section,
+    /// then each entry of the `big_files` section, in the order they are stored.
+    /// The `NodeHeader` is given the index 0.
+    pub fn dump_unknowns<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "section,index,field,value")?;
+        let node = &self.node_header;
+        for (field, value) in [("unk1", node.unk1), ("unk2", node.unk2), ("unk3", node.unk3 as u32), ("unk4", node.unk4 as u32)] {
+            writeln!(out, "node_header,0,{},{:#x}", field, value)?;
+        }
+        for (i, big_hash) in self.big_hashes().enumerate() {
+            let fields = [
+                ("unk3", big_hash.unk3),
+                ("unk4", big_hash.unk4 as u32),
+                ("unk5", big_hash.unk5 as u32),
+                ("unk6", big_hash.unk6 as u32),
+                ("unk7", big_hash.unk7 as u32),
+                ("unk8", big_hash.unk8 as u32),
+                ("unk9", big_hash.unk9 as u32),
+            ];
+            for (field, value) in fields {
+                writeln!(out, "big_hashes,{},{},{:#x}", i, field, value)?;
+            }
+        }
+        for (i, big_file) in self.big_files().enumerate() {
+            writeln!(out, "big_files,{},unk3,{:#x}", i, big_file.unk3)?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote `field` when it contains a character that has a meaning in CSV.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
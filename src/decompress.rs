@@ -0,0 +1,58 @@
+use std::fmt;
+
+use crate::GetFileError;
+
+/// Decompresses the data of files, set with `ParseOptions::decompressor`.
+///
+/// Implement this to use a different zstd implementation than the `zstd` crate.
+pub trait Decompressor: Send + Sync {
+    /// Decompress the zstd compressed `input`, which is `decomp_size` bytes once decompressed.
+    fn decompress(&self, input: &[u8], decomp_size: usize) -> Result<Vec<u8>, GetFileError>;
+}
+
+impl fmt::Debug for dyn Decompressor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Decompressor")
+    }
+}
+
+/// Decompresses with the `zstd` crate, this is the default `Decompressor`.
+#[derive(Debug, Default)]
+pub struct ZstdDecompressor;
+
+impl Decompressor for ZstdDecompressor {
+    fn decompress(&self, input: &[u8], decomp_size: usize) -> Result<Vec<u8>, GetFileError> {
+        let mut buffer_decomp = vec!(0; decomp_size);
+        let bytes_copied = zstd::block::decompress_to_buffer(input, &mut buffer_decomp)
+            .map_err(|error| GetFileError::DecompressFailed { decomp_size, error })?;
+        buffer_decomp.truncate(bytes_copied);
+        Ok(buffer_decomp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressed() -> Vec<u8> {
+        zstd::block::compress(&[0x42; 0x1000], 0).unwrap()
+    }
+
+    #[test]
+    fn decompress() {
+        assert_eq!(ZstdDecompressor.decompress(&compressed(), 0x1000).unwrap(), vec!(0x42; 0x1000));
+    }
+
+    #[test]
+    fn truncated_is_decompress_failed() {
+        let data = compressed();
+        let result = ZstdDecompressor.decompress(&data[..data.len() - 1], 0x1000);
+        assert!(matches!(result, Err(GetFileError::DecompressFailed { decomp_size: 0x1000, .. })));
+    }
+
+    #[test]
+    fn small_decomp_size_is_decompress_failed() {
+        let result = ZstdDecompressor.decompress(&compressed(), 0x100);
+        assert!(matches!(result, Err(GetFileError::DecompressFailed { decomp_size: 0x100, .. })));
+    }
+}
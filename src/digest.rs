@@ -0,0 +1,14 @@
+use sha2::{Digest, Sha256};
+
+use crate::DataArc;
+
+impl DataArc {
+    /// The SHA-256 of the `ArcHeader` followed by the `NodeHeader`, exactly as they are stored in the data.arc.
+    ///
+    /// The headers hold the offset of every section and the number of entries in each,
+    /// so this changes when a tool rewrites the data.arc, e.g. to add files.
+    /// Only the headers are hashed, so a change to file data that keeps every size and offset the same is not detected.
+    pub fn header_digest(&self) -> [u8; 32] {
+        Sha256::digest(&self.raw_headers).into()
+    }
+}
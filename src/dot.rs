@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use scroll::{Pread, LE};
+
+use crate::DataArc;
+use crate::parse::*;
+
+impl DataArc {
+    /// Describe how the sections refer to each other as a graphviz DOT graph.
+    ///
+    /// Starting from the first tree entries, links are drawn from each tree entry to its big hash and its `FileEntry`,
+    /// and from each big hash to its big file, which the offset of the `FileEntry` is relative to.
+    /// Only the first `max_trees` tree entries are included as a full data.arc is far too large to draw.
+    pub fn to_dot(&self, max_trees: usize) -> String {
+        let big_file_count = self.node_header.file_count1 as usize + self.node_header.file_count2 as usize;
+        let name = |hash| match &self.labels {
+            // Escaped to be used within a quoted label
+            Some(labels) => labels.resolve_or_hex(hash).replace('\\', "\\\\").replace('"', "\\\""),
+            None => format!("0x{:x}", hash),
+        };
+
+        let mut dot = String::new();
+        let mut seen = HashSet::new();
+        writeln!(dot, "digraph data_arc {{").unwrap();
+        writeln!(dot, "    node [shape=box];").unwrap();
+        for (i, tree) in self.tree_entries().iter().enumerate().take(max_trees) {
+            writeln!(dot, "    tree_{} [label=\"tree {}\\n{}\"];", i, i, name(tree.path.hash)).unwrap();
+            if let Some(index) = self.file_entry_index(tree) {
+                let file_entry = &self.file_entries()[index];
+                if seen.insert(format!("file_entry_{}", index)) {
+                    writeln!(dot, "    file_entry_{} [label=\"sub_files1 {}\\noffset {:#x}\\nsize {:#x}\"];", index, index, file_entry.offset as u64 * 4, file_entry.comp_size).unwrap();
+                }
+                writeln!(dot, "    tree_{} -> file_entry_{};", i, index).unwrap();
+            }
+
+            let big_hash_index = tree.path.meta as usize;
+            let big_hash = if big_hash_index < self.node_header.folder_count as usize { self.big_hash_entry(big_hash_index) } else { None };
+            if let Some(big_hash) = big_hash {
+                if seen.insert(format!("big_hash_{}", big_hash_index)) {
+                    writeln!(dot, "    big_hash_{} [label=\"big_hashes {}\\n{}\"];", big_hash_index, big_hash_index, name(big_hash.path.hash)).unwrap();
+
+                    let big_file_index = big_hash.path.meta as usize;
+                    let big_file: Option<BigFileEntry> = if big_file_index < big_file_count {
+                        self.buffer.get(self.big_files + BIG_FILE_ENTRY_SIZE * big_file_index ..).and_then(|x| x.pread_with(0, LE).ok())
+                    } else {
+                        None
+                    };
+                    if let Some(big_file) = big_file {
+                        if seen.insert(format!("big_file_{}", big_file_index)) {
+                            writeln!(dot, "    big_file_{} [label=\"big_files {}\\noffset {:#x}\"];", big_file_index, big_file_index, big_file.offset).unwrap();
+                        }
+                        writeln!(dot, "    big_hash_{} -> big_file_{};", big_hash_index, big_file_index).unwrap();
+                    }
+                }
+                writeln!(dot, "    tree_{} -> big_hash_{};", i, big_hash_index).unwrap();
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
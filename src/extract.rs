@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+
+use failure::format_err;
+
+use crate::Backend;
+use crate::{DataArc, FileLocation, TreeEntry, GetFileError, check_compression, decompress, hash40};
+
+/// What `DataArc::extract_where` would write, returned by `DataArc::plan_extraction`.
+#[derive(Debug)]
+pub struct ExtractionPlan {
+    /// The path each file would be written to relative to the output folder, with the size of the file once decompressed
+    pub files: Vec<(String, u64)>,
+    /// Sum of the sizes of `files`
+    pub total_bytes: u64,
+}
+
+impl DataArc {
+    /// Extract every file in the folder with the hash40 `folder_hash` and all of its subfolders to `out`.
+    ///
+    /// Files are written to their path resolved from the labels, relative to `out`.
+    /// Files whose path cannot be resolved are written to `out/0x{hash:x}.bin` instead so that none are skipped.
+    /// Returns the number of files extracted.
+    pub fn extract_folder(&mut self, folder_hash: u64, out: &Path) -> Result<usize, GetFileError> {
+        let subtree = self.folder_subtree(folder_hash);
+        self.extract_where(|tree| subtree.contains(&tree.folder.hash), out)
+    }
+
+    /// Extract every file whose tree entry `pred` returns true for to `out`.
+    ///
+    /// Files are written to the same paths as `extract_folder`.
+    /// Files that can't be located, e.g. redirects, are skipped, the same as in `plan_extraction`.
+    /// Returns the number of files extracted.
+    pub fn extract_where<F: Fn(&TreeEntry) -> bool>(&mut self, pred: F, out: &Path) -> Result<usize, GetFileError> {
+        let files = self.locate_where(pred);
+        for (path_hash, location) in &files {
+            let path = self.output_path(*path_hash, out);
+            self.extract_location(location, path)?;
+        }
+        Ok(files.len())
+    }
+
+    /// Find what `extract_where` would write with `pred`, without reading any file data.
+    ///
+    /// Files that can't be located are left out, the same as `extract_where` skips them.
+    pub fn plan_extraction<F: Fn(&TreeEntry) -> bool>(&self, pred: F) -> ExtractionPlan {
+        let mut files = vec!();
+        let mut total_bytes = 0;
+        for (path_hash, location) in self.locate_where(pred) {
+            let size = location.sub_file.decomp_size as u64;
+            let path = self.output_path(path_hash, Path::new(""));
+            files.push((path.to_string_lossy().into_owned(), size));
+            total_bytes += size;
+        }
+        ExtractionPlan { files, total_bytes }
+    }
+
+    /// The hash40 of the path and the location of every file whose tree entry `pred` returns true for, skipping those that can't be located.
+    fn locate_where<F: Fn(&TreeEntry) -> bool>(&self, pred: F) -> Vec<(u64, FileLocation)> {
+        self.tree_entries().iter()
+            .filter(|tree| pred(tree))
+            .filter_map(|tree| Some((tree.path.hash, self.locate_tree(tree).ok()?)))
+            .collect()
+    }
+
+    /// Extract every file in `paths` to that path relative to `out`.
+    ///
+    /// The files are read in the order they are stored in the data.arc rather than the order of `paths`,
+    /// turning many random seeks into a near sequential read.
+    /// Returns the path written to or the error of each file, in the same order as `paths`,
+    /// so a file that can't be extracted doesn't stop the rest from being extracted.
+    pub fn extract_many(&mut self, paths: &[&str], out: &Path) -> Result<Vec<Result<PathBuf, GetFileError>>, GetFileError> {
+        if self.backend.is_none() {
+            return Err(GetFileError::MetadataOnly);
+        }
+
+        let mut results: Vec<Result<PathBuf, GetFileError>> = Vec::with_capacity(paths.len());
+        let mut pending = vec!();
+        for (i, &file_name) in paths.iter().enumerate() {
+            match self.locate(file_name) {
+                Ok(location) => {
+                    pending.push((i, location));
+                    results.push(Err(GetFileError::FileNotFound)); // Replaced once the file is extracted
+                }
+                Err(err) => results.push(Err(err)),
+            }
+        }
+        pending.sort_by_key(|(_, location)| location.offset);
+
+        for (i, location) in pending {
+            let path = relative_path(paths[i], out).unwrap_or_else(|| out.join(format!("0x{:x}.bin", hash40(paths[i]))));
+            results[i] = self.extract_location(&location, path);
+        }
+        Ok(results)
+    }
+
+    /// The same as `extract_many` but the files are split between `threads` threads, which read and decompress them in parallel.
+    ///
+    /// Requires the data.arc to be parsed with `DataArc::parse_owned` or with `ParseOptions::mmap` of the `mmap` feature,
+    /// so that every thread can read from it at once, see `DataArc::supports_concurrent_reads`.
+    /// Each thread extracts an equal share of `paths`, in the order of `paths`.
+    /// Returns the path written to or the error of each file, in the same order as `paths`.
+    pub fn extract_many_parallel(&self, paths: &[&str], out: &Path, threads: usize) -> Result<Vec<Result<PathBuf, GetFileError>>, GetFileError> {
+        let data = match &self.backend {
+            #[cfg(feature = "mmap")]
+            Some(Backend::Mmap(mmap)) => &mmap[..],
+            Some(Backend::Memory(data)) => &data[..],
+            Some(_) => return Err(format_err!("Failed to extract in parallel: The data.arc is not memory mapped or held in memory").into()),
+            None => return Err(GetFileError::MetadataOnly),
+        };
+
+        let chunk_size = paths.len().div_ceil(threads.max(1)).max(1);
+        let mut results = Vec::with_capacity(paths.len());
+        thread::scope(|scope| {
+            let handles: Vec<_> = paths.chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || {
+                    chunk.iter().map(|&file_name| self.extract_mapped(data, file_name, out)).collect::<Vec<_>>()
+                }))
+                .collect();
+            for handle in handles {
+                results.extend(handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)));
+            }
+        });
+        Ok(results)
+    }
+
+    /// Extract the file at `file_name` from `data`, the entire data.arc, to that path relative to `out`, returning the path written to.
+    fn extract_mapped(&self, data: &[u8], file_name: &str, out: &Path) -> Result<PathBuf, GetFileError> {
+        let location = self.locate(file_name)?;
+        let path = relative_path(file_name, out).unwrap_or_else(|| out.join(format!("0x{:x}.bin", hash40(file_name))));
+        check_compression(&location.sub_file)?;
+        let data = decompress(&*self.decompressor, &location.sub_file, data[location.range()].to_vec())?;
+        write_file(path, &data)
+    }
+
+    /// The hash40 of `folder_hash` and all of its subfolders, found by following the parent of each folder.
+    fn folder_subtree(&self, folder_hash: u64) -> HashSet<u64> {
+        let parents: HashMap<u64, u64> = self.big_hashes().map(|x| (x.path.hash, x.parent.hash)).collect();
+
+        let mut subtree = HashSet::new();
+        subtree.insert(folder_hash);
+        for &folder in parents.keys() {
+            // Limit the number of parents followed in case the parents form a loop.
+            let mut current = folder;
+            for _ in 0..parents.len() {
+                if current == folder_hash {
+                    subtree.insert(folder);
+                    break;
+                }
+                match parents.get(&current) {
+                    Some(&parent) => current = parent,
+                    None => break,
+                }
+            }
+        }
+        subtree
+    }
+
+    /// Extract the file stored at `location` to `path`, returning the path written to.
+    fn extract_location(&mut self, location: &FileLocation, path: PathBuf) -> Result<PathBuf, GetFileError> {
+        check_compression(&location.sub_file)?;
+        let buffer_comp = self.read_stored(location)?;
+        let data = decompress(&*self.decompressor, &location.sub_file, buffer_comp)?;
+        write_file(path, &data)
+    }
+
+    /// Where to extract the file with the hash40 `path_hash` to.
+    /// Uses the path resolved from the labels relative to `out`, falling back to `out/0x{hash:x}.bin`.
+    fn output_path(&self, path_hash: u64, out: &Path) -> PathBuf {
+        self.labels.as_ref()
+            .and_then(|x| x.resolve(path_hash))
+            .and_then(|label| relative_path(label, out))
+            .unwrap_or_else(|| out.join(format!("0x{:x}.bin", path_hash)))
+    }
+}
+
+/// Write `data` to `path`, creating the folders containing it, returning the path written to.
+fn write_file(path: PathBuf, data: &[u8]) -> Result<PathBuf, GetFileError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|error| GetFileError::WriteFailed { path: path.clone(), error })?;
+    }
+    fs::write(&path, data).map_err(|error| GetFileError::WriteFailed { path: path.clone(), error })?;
+    Ok(path)
+}
+
+/// `path` relative to `out`, or `None` if `path` has no normal components.
+fn relative_path(path: &str, out: &Path) -> Option<PathBuf> {
+    // Only keep normal components so a path can't escape `out`.
+    let relative: PathBuf = Path::new(path).components()
+        .filter(|x| matches!(x, Component::Normal(_)))
+        .collect();
+    if relative.components().next().is_some() {
+        Some(out.join(relative))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{ArcBuilder, DataArc};
+    use crate::parse::*;
+
+    #[test]
+    fn plan_matches_extract_where() {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/first.bin", b"first", true);
+        builder.add_file("a/redirect.bin", b"redirect", false);
+        builder.add_file("a/third.bin", b"third", false);
+        let mut data = builder.build().unwrap();
+        // Mark the second tree entry as a redirect, which can't be located
+        let trees = DataArc::parse_bytes(&data).unwrap().section_ranges()["trees"].start;
+        let flags = LittleEndian::read_u64(&data[0x20..]) as usize + NODE_HEADER_SIZE + trees + TREE_ENTRY_SIZE + 0x24;
+        LittleEndian::write_u32(&mut data[flags..], 0x200000);
+        let mut data_arc = DataArc::parse_owned(data).unwrap();
+
+        let plan = data_arc.plan_extraction(|_| true);
+        let out = std::env::temp_dir().join(format!("ultimate_data_arc_extract_{}", std::process::id()));
+        let extracted = data_arc.extract_where(|_| true, &out).unwrap();
+        let mut written: Vec<String> = fs::read_dir(&out).unwrap().map(|x| x.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        fs::remove_dir_all(&out).unwrap();
+
+        assert_eq!(extracted, 2);
+        assert_eq!(plan.files.len(), extracted);
+        assert_eq!(plan.total_bytes, 10);
+        let mut planned: Vec<String> = plan.files.into_iter().map(|(path, _)| path).collect();
+        planned.sort();
+        written.sort();
+        assert_eq!(written, planned);
+    }
+
+    #[test]
+    fn extract_many_parallel_from_memory() {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/first.bin", b"first", true);
+        builder.add_file("a/b/second.bin", b"second", false);
+        let data_arc = DataArc::parse_owned(builder.build().unwrap()).unwrap();
+        assert!(data_arc.supports_concurrent_reads());
+
+        let out = std::env::temp_dir().join(format!("ultimate_data_arc_parallel_{}", std::process::id()));
+        let results = data_arc.extract_many_parallel(&["a/first.bin", "a/b/second.bin", "a/missing.bin"], &out, 2).unwrap();
+        let first = fs::read(out.join("a/first.bin")).unwrap();
+        let second = fs::read(out.join("a/b/second.bin")).unwrap();
+        fs::remove_dir_all(&out).unwrap();
+
+        assert_eq!((first.as_slice(), second.as_slice()), (&b"first"[..], &b"second"[..]));
+        assert!(results[0].is_ok() && results[1].is_ok() && results[2].is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use crate::DataArc;
+
+/// A tree entry joined with the file entry of its data, returned by `DataArc::index_rows`.
+#[derive(Debug, Clone, Copy)]
+pub struct IndexRow {
+    /// hash40 of the full path of the file
+    pub path_hash: u64,
+    /// hash40 of the extension of the file
+    pub ext_hash: u64,
+    /// hash40 of the folder containing the file
+    pub folder_hash: u64,
+    /// Absolute offset of the data in the data.arc
+    pub abs_offset: u64,
+    /// Size of the file as stored in the data.arc
+    pub comp_size: u32,
+    /// Size of the file once decompressed
+    pub decomp_size: u32,
+    /// The `flags` of the `FileEntry`
+    pub flags: u32,
+}
+
+/// Where the data of a file entry is stored, returned by `DataArc::physical_files`.
+#[derive(Debug, Clone, Copy)]
+pub struct PhysicalFile {
+    /// Absolute offset of the data in the data.arc
+    pub abs_offset: u64,
+    /// Size of the data as stored in the data.arc
+    pub comp_size: u64,
+    /// Size of the data once decompressed
+    pub decomp_size: u64,
+    pub compressed: bool,
+}
+
+impl DataArc {
+    /// A row for each tree entry whose data can be located, in the order they are stored in the `trees` section.
+    ///
+    /// The rows are created as the iterator is advanced, so this does not allocate all of them up front.
+    /// Redirects and tree entries whose data can't be located are skipped.
+    pub fn index_rows(&self) -> impl Iterator<Item = IndexRow> + '_ {
+        self.tree_entries().iter()
+            .filter_map(move |tree| {
+                let location = self.locate_tree(tree).ok()?;
+                Some(IndexRow {
+                    path_hash: tree.path.hash,
+                    ext_hash: tree.ext.hash,
+                    folder_hash: tree.folder.hash,
+                    abs_offset: location.offset,
+                    comp_size: location.sub_file.comp_size,
+                    decomp_size: location.sub_file.decomp_size,
+                    flags: location.sub_file.flags,
+                })
+            })
+    }
+
+    /// Where the data of every member of every big file is stored, in the order the big files are stored in the `big_files` section.
+    ///
+    /// This is calculated from the metadata only, so the data is not read and may not fit within the data.arc when it is corrupt.
+    /// Members whose `FileEntry` does not exist or whose offset overflows a u64 are skipped.
+    pub fn physical_files(&self) -> impl Iterator<Item = PhysicalFile> + '_ {
+        self.big_files().flat_map(move |big_file| {
+            let file_entries = self.file_entries();
+            let start = big_file.suboffset_index as usize;
+            let end = start.saturating_add(big_file.files as usize).min(file_entries.len());
+            file_entries.get(start..end).unwrap_or(&[]).iter().filter_map(move |file_entry| Some(PhysicalFile {
+                abs_offset: self.file_data_offset(&big_file, file_entry)?,
+                comp_size: file_entry.comp_size as u64,
+                decomp_size: file_entry.decomp_size as u64,
+                compressed: !file_entry.suboffset_decompressed(),
+            }))
+        })
+    }
+
+    /// The largest power of two that the offset of every file in `physical_files` is a multiple of, e.g. 0x10.
+    ///
+    /// The offsets are taken relative to the start of the file section, so the alignment of the section itself doesn't matter.
+    /// Returns `None` when there are no files or every file is at the start of the file section, as there is nothing to measure.
+    pub fn offset_alignment(&self) -> Option<u64> {
+        let file_section_offset = self.file_section_start();
+        let offsets = self.physical_files().fold(0, |acc, file| acc | (file.abs_offset - file_section_offset));
+        if offsets == 0 {
+            None
+        } else {
+            Some(1 << offsets.trailing_zeros())
+        }
+    }
+
+    /// Every offset in `physical_files` that more than one file entry stores its data at, with the number of file entries that do.
+    ///
+    /// Sorted by the number of file entries, most first, then by the offset.
+    /// This only compares offsets, files with the same contents stored at different offsets are not found.
+    pub fn reused_offsets(&self) -> Vec<(u64, usize)> {
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        for file in self.physical_files() {
+            *counts.entry(file.abs_offset).or_insert(0) += 1;
+        }
+        let mut reused: Vec<_> = counts.into_iter().filter(|&(_, count)| count > 1).collect();
+        reused.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        reused
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::hash40;
+
+/// Maps hash40s back to the strings they were computed from.
+///
+/// The data.arc only stores hashes of paths, folders, filenames and extensions.
+/// Any string that may have been hashed can be added here to recover it from its hash.
+#[derive(Debug, Default)]
+pub struct HashLabels {
+    labels: HashMap<u64, String>,
+}
+
+impl HashLabels {
+    pub fn new() -> HashLabels {
+        HashLabels::default()
+    }
+
+    /// Load the labels from a file with a label on each line, such as the `Hashes.txt` lists shared by modding tools.
+    ///
+    /// Empty lines are skipped.
+    pub fn from_file(path: &Path) -> io::Result<HashLabels> {
+        let mut labels = HashLabels::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim_end_matches('\r');
+            if !line.is_empty() {
+                labels.add(line);
+            }
+        }
+        Ok(labels)
+    }
+
+    /// Add a label, it will be stored under its hash40.
+    pub fn add(&mut self, label: &str) {
+        self.labels.insert(hash40(label), label.to_string());
+    }
+
+    /// Find the label with the passed hash40.
+    pub fn resolve(&self, hash: u64) -> Option<&str> {
+        self.labels.get(&hash).map(|x| x.as_str())
+    }
+
+    /// Find the label with the passed hash40, falling back to the hash formatted as `0x{hash:x}`.
+    pub fn resolve_or_hex(&self, hash: u64) -> String {
+        match self.resolve(hash) {
+            Some(label) => label.to_string(),
+            None => format!("0x{:x}", hash),
+        }
+    }
+}
+
+/// How many of the file paths in a data.arc are named by its labels, returned by `DataArc::name_coverage`.
+#[derive(Debug)]
+pub struct NameCoverage {
+    /// The number of distinct file path hashes
+    pub total_hashes: usize,
+    /// The number of distinct file path hashes that have a label
+    pub named: usize,
+    /// The file path hashes without a label, in the order they are stored
+    pub unnamed: Vec<u64>,
+}
+
+/// A file path hash without a label, returned by `DataArc::unnamed_entries`.
+#[derive(Debug)]
+pub struct UnnamedEntry {
+    pub path_hash: u64,
+    /// The label of the extension of the file, when there is one
+    pub ext: Option<String>,
+    /// The label of the folder containing the file, when there is one
+    pub folder: Option<String>,
+}
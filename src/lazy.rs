@@ -0,0 +1,25 @@
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// The entries of a section of the node section, decoded the first time they are accessed.
+pub(crate) struct LazySection<T> {
+    /// Where the section is in the node section, empty when the section could not be found
+    range: Range<usize>,
+    entry_size: usize,
+    decode: fn(&[u8]) -> T,
+    entries: OnceLock<Vec<T>>,
+}
+
+impl<T> LazySection<T> {
+    /// `range` must be within the node section that will be passed to `get`.
+    pub(crate) fn new(range: Range<usize>, entry_size: usize, decode: fn(&[u8]) -> T) -> LazySection<T> {
+        LazySection { range, entry_size, decode, entries: OnceLock::new() }
+    }
+
+    /// The entries of the section, decoding them from `buffer` if they haven't been already.
+    pub(crate) fn get(&self, buffer: &[u8]) -> &[T] {
+        self.entries.get_or_init(|| {
+            buffer[self.range.clone()].chunks_exact(self.entry_size).map(self.decode).collect()
+        })
+    }
+}
@@ -1,36 +1,518 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Error as IOError};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom, Error as IOError};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use byteorder::{LittleEndian, ByteOrder, ReadBytesExt};
 use failure::{Error, format_err};
 use scroll::{Pread, LE, Error as ScrollError};
 
+mod builder;
+mod cache;
+mod check;
+mod csv;
+mod decompress;
+mod dot;
+mod extract;
+mod index;
+mod labels;
+mod lazy;
+mod options;
 mod parse;
+mod probe;
+mod profile;
+mod sniff;
+mod stream;
+#[cfg(feature = "digest")]
+mod digest;
+#[cfg(feature = "test-util")]
+pub mod testutil;
+use crate::check::is_pre_300_layout;
+use crate::lazy::LazySection;
 use crate::parse::*;
 
+pub use crate::builder::ArcBuilder;
+pub use crate::check::{ArcVersion, IndexError};
+pub use crate::decompress::{Decompressor, ZstdDecompressor};
+pub use crate::extract::ExtractionPlan;
+pub use crate::index::{IndexRow, PhysicalFile};
+pub use crate::labels::{HashLabels, NameCoverage, UnnamedEntry};
+pub use crate::options::ParseOptions;
+pub use crate::probe::CompressionProbe;
+pub use crate::profile::ArcProfile;
+pub use crate::parse::{ArcHeader, BigFileEntry, BigHashEntry, Compression, EntryPair, EntryTriplet, FileEntry, FilePair, NodeHeader, SectionEntry, TreeEntry, TreeFlags};
+pub use crate::stream::{ArcVisitor, TreeEntryStream, parse_streaming};
+
+/// The magic number every data.arc file starts with
+pub const DATA_ARC_MAGIC: u64 = 0xabcdef9876543210;
+
+/// Check if `reader` starts with the magic number of a data.arc, without parsing it.
+///
+/// The magic number is read from the current position, which `reader` is returned to afterwards.
+/// Any IO error is treated as `reader` not being a data.arc, use `check_magic` to handle them instead.
+pub fn is_data_arc<R: Read + Seek>(reader: &mut R) -> bool {
+    check_magic(reader).unwrap_or(false)
+}
+
+/// Check if `reader` starts with the magic number of a data.arc, without parsing it.
+///
+/// The magic number is read from the current position, which `reader` is returned to afterwards,
+/// so the same reader can then be parsed without reopening it.
+/// A reader with less than 8 bytes left is not a data.arc, this is not an error.
+pub fn check_magic<R: Read + Seek>(reader: &mut R) -> Result<bool, IOError> {
+    let start = reader.stream_position()?;
+    let magic = match reader.read_u64::<LittleEndian>() {
+        Ok(magic) => Some(magic),
+        Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => None,
+        Err(err) => return Err(err),
+    };
+    reader.seek(SeekFrom::Start(start))?;
+    Ok(magic == Some(DATA_ARC_MAGIC))
+}
+
 /// The data.arc file starts with a magic number to identify it as a data.arc
-/// It is assumed that any error that occurs on a file starting with the magic number is an internal error
-/// i.e. a bug that needs to be fixed.
+/// Corrupt data.arcs that are detected have their own variant,
+/// any other error that occurs on a file starting with the magic number is assumed to be an internal error i.e. a bug that needs to be fixed.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ParseError {
-    /// The file doesn't start with the magic number 0xabcdef9876543210 so it is not a data.arc file
+    /// The file doesn't start with the magic number 0xabcdef9876543210, or `ParseOptions::expected_magic`, so it is not a data.arc file
     NotDataArc,
+    /// The file is empty or ends before the end of the data.arc header, e.g. a placeholder or partially written file
+    Truncated {
+        /// Size of the file from the magic offset
+        len: u64,
+    },
+    /// The size of the node section is too small to hold the node header or is larger than the rest of the data.arc,
+    /// so the data.arc is corrupt
+    InvalidNodeSize {
+        /// Size of the node section as read from the data.arc
+        size: u64,
+        /// Bytes in the data.arc from the start of the node section to the end of the data.arc
+        remaining: u64,
+    },
+    /// The compressed node section decompressed to a different size than the CompressedNodeHeader says,
+    /// so the data.arc is corrupt
+    DecompressSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The compressed node section could not be decompressed to the size the CompressedNodeHeader says,
+    /// because the zstd data is truncated or corrupt or the size is too small, so the data.arc is corrupt
+    DecompressFailed {
+        /// The size the node section should decompress to
+        decomp_size: usize,
+        /// The error from zstd
+        error: IOError,
+    },
+    /// The node section is larger than `ParseOptions::max_node_size` once decompressed,
+    /// so the data.arc is corrupt or the limit needs to be raised
+    NodeTooLarge {
+        size: u64,
+        max_size: u64,
+    },
+    /// A count of entries in the node header is larger than `ParseOptions::max_entries_per_section`,
+    /// so the data.arc is corrupt or the limit needs to be raised
+    SectionOverrun {
+        /// The name of the count, e.g. `tree_count`
+        count_name: &'static str,
+        count: u32,
+        max_entries: u64,
+    },
+    /// An entry indexes past the end of another section, so the data.arc is corrupt.
+    /// Only returned when `ParseOptions::validate_indices` is set.
+    InvalidIndices (Vec<IndexError>),
+    /// Parsing the data.arc again with `DataArc::reparse_self` found that it no longer matches what was parsed before,
+    /// or that it fails `DataArc::self_check`. Each string describes one difference or violation.
+    Mismatch (Vec<String>),
+    /// A section of the node section is not where its entries are, e.g. because a count in the node header is wrong
+    /// or the sections are stored in a different order. Each string describes one misaligned section.
+    /// Only returned when `ParseOptions::check_alignment` is set.
+    Misaligned (Vec<String>),
+    /// Another section of the `ArcHeader` starts within the node section, so the data.arc is corrupt,
+    /// e.g. it was truncated or concatenated with another file
+    SectionOverlap {
+        /// The name of the offset field of the section, e.g. `file_section_offset`
+        section: &'static str,
+        offset: u64,
+        /// The end of the node section as stored, relative to the start of the data.arc
+        node_end: u64,
+    },
+    /// A section of the node section runs past the end of the node section, so a count in the node header is wrong
+    /// or the data.arc is corrupt
+    TruncatedSection {
+        /// The name of the section, e.g. `trees`
+        section: &'static str,
+        /// The offset the section ends at, relative to the end of the node header
+        needed: usize,
+        /// The size of the node section after the node header
+        available: usize,
+    },
+    /// The known sections of the node section end before its last byte that isn't zero, so a count in the node header is wrong
+    /// or the node section has a section that isn't known yet.
+    /// Not returned when `ParseOptions::check_node_size` is turned off.
+    SizeMismatch {
+        /// The offset the `numbers` section ends at, relative to the end of the node header
+        expected: usize,
+        /// The size of the node section after the node header, without the zero padding at its end
+        actual: usize,
+    },
+    /// The node section doesn't have the layout this crate parses, so its entries would be read from the wrong offsets.
+    /// Only returned when `ParseOptions::check_version` is set, see `DataArc::version`.
+    UnsupportedVersion {
+        /// Which layout the node section appears to have, never `ArcVersion::Known`
+        detected: ArcVersion,
+    },
     /// A bug that needs to be fixed
     InternalError (Error)
 }
 
+/// The result of `DataArc::new_lenient`.
+pub struct ParseReport {
+    /// `None` when the data.arc could not be parsed at all
+    pub data_arc: Option<DataArc>,
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// A failure that `DataArc::new_lenient` recovered from.
+#[derive(Debug)]
+pub struct ParseWarning {
+    /// The name of the section that failed to decode, and was left empty.
+    /// Or the section that looks misaligned, see `DataArc::another_hash_table`.
+    /// Or `node` for unknown data after the last section, see `DataArc::trailing_node_bytes`.
+    pub section: &'static str,
+    pub error: ParseError,
+}
+
+/// Totals of the sizes of every file entry, returned by `DataArc::compression_stats`.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionStats {
+    /// Sum of the size of every file as stored in the data.arc
+    pub total_comp: u64,
+    /// Sum of the size of every file once decompressed
+    pub total_decomp: u64,
+    /// `total_decomp / total_comp`, how many times larger the files are once extracted.
+    /// 1.0 when there are no files.
+    pub ratio: f64,
+    /// Number of files that are stored without compression
+    pub uncompressed_file_count: usize,
+}
+
+/// Totals of the files in a folder, returned by `DataArc::folder_stats`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FolderStats {
+    /// Number of tree entries in the folder
+    pub file_count: usize,
+    /// Sum of the size of every file once decompressed
+    pub total_decomp: u64,
+    /// Sum of the size of every file as stored in the data.arc
+    pub total_comp: u64,
+}
+
+/// The data of a file both as stored and decompressed, returned by `DataArc::read_file_full`.
+#[derive(Debug)]
+pub struct FileData {
+    /// The data exactly as it is stored in the data.arc
+    pub raw: Vec<u8>,
+    /// The data once decompressed, equal to `raw` when the file is stored uncompressed
+    pub decompressed: Vec<u8>,
+    pub compressed: bool,
+}
+
+/// Where the node section is stored, returned by `DataArc::node_section_range`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NodeSection {
+    /// Absolute offset of the node section in the data.arc
+    pub offset: u64,
+    /// Size of the node section as stored in the data.arc.
+    /// When compressed this is the size of the CompressedNodeHeader and the zstd data that follows it,
+    /// otherwise it is the `file_size` of the NodeHeader.
+    pub size: u64,
+    /// If the node section is stored compressed with zstd
+    pub compressed: bool,
+    pub format: NodeFormat,
+}
+
+/// How the node section is stored, returned by `DataArc::node_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeFormat {
+    /// Compressed with zstd, following a CompressedNodeHeader
+    Compressed {
+        /// Size of the zstd data
+        comp_size: u32,
+        /// Size of the node section once decompressed
+        decomp_size: u32,
+    },
+    /// Stored as is, `size` is the `file_size` of the NodeHeader
+    Uncompressed { size: u32 },
+}
+
+impl Default for NodeFormat {
+    fn default() -> NodeFormat {
+        NodeFormat::Uncompressed { size: 0 }
+    }
+}
+
+/// An entry of the `bulkfile_category_info` section, returned by `DataArc::movie_categories`.
+///
+/// The meaning of the fields is tentative and has not been verified against a data.arc:
+/// they are laid out like a category of bulkfiles holding the `count` bulkfiles starting at `start`
+/// in the `bulkfile_hash_lookup` and `bulkfiles_by_name` sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieCategory {
+    /// The hash40 of the name of the category
+    pub hash: u64,
+    /// The `meta2` of the entry, the index of the first bulkfile in the category
+    pub start: u32,
+    /// The `meta` of the entry, the number of bulkfiles in the category
+    pub count: u32,
+}
+
+/// A file found in two data.arcs whose data differs between them, returned by `DataArc::content_diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentChange {
+    /// The hash40 of the path of the file
+    pub path_hash: u64,
+    pub kind: ContentChangeKind,
+}
+
+/// How the data of a file differs between two data.arcs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentChangeKind {
+    /// The contents differ once decompressed
+    Modified,
+    /// The contents are the same once decompressed but are stored at a different offset
+    Moved,
+}
+
+/// Where file data is read from, returned by `DataArc::backend_kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Read from the file with a seek and read for each file, so reads can't happen at once
+    File,
+    /// Read from the memory mapped file, see `ParseOptions::mmap`
+    Mmap,
+    /// Read from the bytes of the data.arc held in memory, see `DataArc::parse_owned`
+    Memory,
+    /// Read from the reader with a seek and read for each file, see `DataArc::from_reader`
+    Reader,
+    /// No file data can be read, see `DataArc::new_metadata_only`
+    MetadataOnly,
+}
+
 /// Why file data could not be retrieved.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum GetFileError {
     /// The passed filename or hash does not match any files
     FileNotFound,
+    /// The data of the file would extend past the end of the data.arc, so the data.arc is corrupt
+    SectionOverrun {
+        /// Absolute offset of the data in the data.arc, `u64::MAX` when the offset overflows
+        offset: u64,
+        /// Size of the data in the data.arc
+        size: u64,
+        /// Size of the entire data.arc
+        file_len: u64,
+    },
+    /// The `DataArc` was created by `DataArc::new_metadata_only` so there is no file to read the data from
+    MetadataOnly,
+    /// The file decompressed to a different size than its `FileEntry` says,
+    /// so the data.arc is corrupt or the `Decompressor` is wrong
+    DecompressSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The file could not be decompressed to the size its `FileEntry` says,
+    /// because its data is truncated or corrupt or the size is too small, so the data.arc is corrupt or the `Decompressor` is wrong
+    DecompressFailed {
+        /// The size the file should decompress to
+        decomp_size: usize,
+        /// The error from the `Decompressor`
+        error: IOError,
+    },
+    /// `DataArc::read_big_file_member` was passed a member past the end of the big file
+    MemberOutOfRange {
+        member: u32,
+        /// Number of members in the big file
+        files: u32,
+    },
+    /// The `flags` of the `FileEntry` of the file give a compression other than zstd, which is not supported
+    UnsupportedCompression {
+        flags: u32,
+    },
+    /// The tree entry of the file is a redirect to another file, or shares the `FileEntry` of another file,
+    /// neither of which can be followed yet
+    UnsupportedRedirect {
+        /// The hash40 of the path of the tree entry
+        path: u64,
+        /// The `flags` of the tree entry
+        flags: u32,
+    },
+    /// The extracted file could not be written to `path`
+    WriteFailed {
+        path: PathBuf,
+        error: IOError,
+    },
     /// A bug that needs to be fixed
     InternalError (Error)
 }
 
+// The zstd crate reports its errors as `std::io::Error` so those are covered too.
+impl From<IOError> for ParseError {
+    fn from(err: IOError) -> ParseError {
+        ParseError::InternalError(err.into())
+    }
+}
+
+impl From<ScrollError> for ParseError {
+    fn from(err: ScrollError) -> ParseError {
+        ParseError::InternalError(err.into())
+    }
+}
+
+impl From<Error> for ParseError {
+    fn from(err: Error) -> ParseError {
+        ParseError::InternalError(err)
+    }
+}
+
+/// Allows `?` on a `ParseError` in a function returning `std::io::Result`.
+///
+/// A data.arc that isn't one or is corrupt is `InvalidData` and a truncated data.arc is `UnexpectedEof`.
+/// An `InternalError` caused by an IO error is converted back to that IO error, any other `InternalError` is `Other`.
+impl From<ParseError> for IOError {
+    fn from(err: ParseError) -> IOError {
+        let kind = match err {
+            ParseError::NotDataArc => ErrorKind::InvalidData,
+            ParseError::Truncated { .. } => ErrorKind::UnexpectedEof,
+            ParseError::InvalidNodeSize { .. } => ErrorKind::InvalidData,
+            ParseError::DecompressSizeMismatch { .. } => ErrorKind::InvalidData,
+            ParseError::DecompressFailed { .. } => ErrorKind::InvalidData,
+            ParseError::NodeTooLarge { .. } => ErrorKind::InvalidData,
+            ParseError::SectionOverrun { .. } => ErrorKind::InvalidData,
+            ParseError::TruncatedSection { .. } => ErrorKind::UnexpectedEof,
+            ParseError::InvalidIndices (_) => ErrorKind::InvalidData,
+            ParseError::Mismatch (_) => ErrorKind::InvalidData,
+            ParseError::Misaligned (_) => ErrorKind::InvalidData,
+            ParseError::SectionOverlap { .. } => ErrorKind::InvalidData,
+            ParseError::SizeMismatch { .. } => ErrorKind::InvalidData,
+            ParseError::UnsupportedVersion { .. } => ErrorKind::InvalidData,
+            ParseError::InternalError (err) => {
+                return match err.downcast::<IOError>() {
+                    Ok(err) => err,
+                    Err(err) => IOError::other(err.to_string()),
+                };
+            }
+        };
+        IOError::new(kind, err.to_string())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::NotDataArc => write!(f, "The file is not a data.arc, it does not start with the magic number"),
+            ParseError::Truncated { len } => write!(f, "The data.arc is truncated, it is only {:#x} bytes", len),
+            ParseError::InvalidNodeSize { size, remaining } => write!(f,
+                "The size of the node section {:#x} is too small for the node header or larger than the {:#x} bytes left in the data.arc",
+                size, remaining
+            ),
+            ParseError::DecompressFailed { decomp_size, error } => write!(f,
+                "The node section could not be decompressed to {:#x} bytes: {}", decomp_size, error
+            ),
+            ParseError::DecompressSizeMismatch { expected, actual } => write!(f,
+                "The node section decompressed to {:#x} bytes instead of {:#x} bytes", actual, expected
+            ),
+            ParseError::NodeTooLarge { size, max_size } => write!(f,
+                "The node section is {:#x} bytes once decompressed, larger than the limit of {:#x} bytes", size, max_size
+            ),
+            ParseError::SectionOverrun { count_name, count, max_entries } => write!(f,
+                "The {} {} is larger than the limit of {} entries", count_name, count, max_entries
+            ),
+            ParseError::TruncatedSection { section, needed, available } => write!(f,
+                "The {} section ends at {:#x}, past the end of the node section of size {:#x}", section, needed, available
+            ),
+            ParseError::InvalidIndices (errors) => {
+                write!(f, "{} indices are out of range", errors.len())?;
+                if let Some(error) = errors.first() {
+                    write!(f, ", the first is the {} {} of {} entry {}, out of range of the {} entries in {}",
+                        error.field, error.index, error.section, error.entry, error.target_len, error.target)?;
+                }
+                Ok(())
+            }
+            ParseError::Mismatch (differences) => write!(f, "The data.arc does not match: {}", differences.join("; ")),
+            ParseError::Misaligned (sections) => write!(f, "The sections are misaligned: {}", sections.join("; ")),
+            ParseError::SectionOverlap { section, offset, node_end } => write!(f,
+                "The {} {:#x} is within the node section, which ends at {:#x}", section, offset, node_end
+            ),
+            ParseError::SizeMismatch { expected, actual } => write!(f,
+                "The known sections end at {:#x} but the node section has data up to {:#x}", expected, actual
+            ),
+            ParseError::UnsupportedVersion { detected } => write!(f,
+                "The node section has the {:?} layout, which is not supported", detected
+            ),
+            ParseError::InternalError (err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// The source of an `InternalError` is the IO or scroll error that caused it and the source of a `DecompressFailed` is the zstd error,
+/// other errors have no source.
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::InternalError (err) => {
+                if let Some(err) = err.downcast_ref::<IOError>() {
+                    Some(err)
+                } else if let Some(err) = err.downcast_ref::<ScrollError>() {
+                    Some(err)
+                } else {
+                    None
+                }
+            }
+            ParseError::DecompressFailed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<IOError> for GetFileError {
+    fn from(err: IOError) -> GetFileError {
+        GetFileError::InternalError(err.into())
+    }
+}
+
+impl From<ScrollError> for GetFileError {
+    fn from(err: ScrollError) -> GetFileError {
+        GetFileError::InternalError(err.into())
+    }
+}
+
+impl From<Error> for GetFileError {
+    fn from(err: Error) -> GetFileError {
+        GetFileError::InternalError(err)
+    }
+}
+
+/// A parsed data.arc.
+///
+/// The metadata is read into memory when the `DataArc` is created.
+/// File data is read lazily, so the `DataArc` owns the `File` it was created from (or its memory map when using
+/// `ParseOptions::mmap`, or the bytes or reader it was parsed from) and keeps it open until the `DataArc` is dropped.
+/// Use `DataArc::new_metadata_only` to close the file as soon as the metadata has been read.
 pub struct DataArc {
-    file: File,
+    backend: Option<Backend>,
+    file_len: u64,
+    /// Offset in the file that the data.arc starts at, all offsets in `header` are relative to this
+    base_offset: u64,
     header: ArcHeader,
+    node_header: NodeHeader,
     buffer: Vec<u8>,
     first_hash_bucket: HashBucket,
 
@@ -50,69 +532,367 @@ pub struct DataArc {
     file_lookup_buckets: usize,
     file_lookup: usize,
     numbers: usize,
+
+    // sections that are decoded when first accessed
+    bulkfile_file_indices: LazySection<u32>,
+    music_files: LazySection<FilePair>,
+    another_hash_entries: LazySection<EntryTriplet>,
+    tree_entries: LazySection<TreeEntry>,
+    /// The `sub_files1` section followed by the `sub_files2` section
+    file_entries: LazySection<FileEntry>,
+
+    labels: Option<HashLabels>,
+    node_section: NodeSection,
+    /// The magic number the data.arc starts with
+    magic: u64,
+    decompressor: Arc<dyn Decompressor>,
+
+    #[cfg(feature = "tokio")]
+    async_file: tokio::sync::Mutex<Option<tokio::fs::File>>,
+    /// The ArcHeader followed by the NodeHeader, exactly as they are stored
+    #[cfg(feature = "digest")]
+    raw_headers: Vec<u8>,
 }
 
 impl DataArc {
     /// Parse the passed `data.arc` file.
-    pub fn new(mut file: File) -> Result<DataArc, ParseError> {
-        if let Ok(magic) = file.read_u64::<LittleEndian>() {
-            if magic != 0xabcdef9876543210 {
-                return Err(ParseError::NotDataArc);
+    /// The file is kept open so that `get_file` can read from it.
+    pub fn new(file: File) -> Result<DataArc, ParseError> {
+        DataArc::new_with_options(file, ParseOptions::new())
+    }
+
+    /// Parse the data.arc at `path` and label it with the labels in the file at `names`, see `HashLabels::from_file`.
+    pub fn open_indexed(path: &Path, names: &Path) -> Result<DataArc, ParseError> {
+        let mut data_arc = DataArc::new(File::open(path)?)?;
+        data_arc.set_labels(HashLabels::from_file(names)?);
+        Ok(data_arc)
+    }
+
+    /// The same as `DataArc::new` but configured by the passed `ParseOptions`.
+    pub fn new_with_options(file: File, options: ParseOptions) -> Result<DataArc, ParseError> {
+        let (data_arc, file) = DataArc::parse(file, &options, None)?;
+        data_arc.with_backend(file, options)
+    }
+
+    /// Parse the passed `data.arc` file, recovering as much as possible from a damaged file.
+    ///
+    /// Sections that fail to decode are recorded as a warning and left empty instead of failing the entire parse.
+    /// When the data.arc can't be parsed at all `data_arc` is `None` and the reason is the last warning.
+    pub fn new_lenient(file: File, options: ParseOptions) -> ParseReport {
+        let mut warnings = vec!();
+        let result = DataArc::parse(file, &options, Some(&mut warnings))
+            .and_then(|(data_arc, file)| data_arc.with_backend(file, options));
+        let data_arc = match result {
+            Ok(data_arc) => Some(data_arc),
+            Err(error) => {
+                warnings.push(ParseWarning { section: "data.arc", error });
+                None
+            }
+        };
+        ParseReport { data_arc, warnings }
+    }
+
+    /// Parse the passed `data.arc` file, closing it once the metadata has been read.
+    /// `get_file` will return `GetFileError::MetadataOnly` on the returned `DataArc`.
+    pub fn new_metadata_only(file: File) -> Result<DataArc, ParseError> {
+        DataArc::parse(file, &ParseOptions::new(), None).map(|(data_arc, _)| data_arc)
+    }
+
+    /// Parse the bytes of a data.arc, keeping them to read file data from, e.g. for a data.arc that was downloaded or built in memory.
+    pub fn parse_owned(data: Vec<u8>) -> Result<DataArc, ParseError> {
+        DataArc::parse_owned_with_options(data, ParseOptions::new())
+    }
+
+    /// The same as `DataArc::parse_owned` but configured by the passed `ParseOptions`.
+    ///
+    /// `ParseOptions::mmap` is ignored, as the data.arc is already in memory.
+    pub fn parse_owned_with_options(data: Vec<u8>, options: ParseOptions) -> Result<DataArc, ParseError> {
+        let data_arc = DataArc::parse_reader(&mut Cursor::new(&data), &options, None)?;
+        let decompressor = match options.decompressor {
+            Some(decompressor) => Arc::from(decompressor),
+            None => data_arc.decompressor,
+        };
+        Ok(DataArc { backend: Some(Backend::Memory(data)), decompressor, ..data_arc })
+    }
+
+    /// Parse the metadata of the data.arc in `data` without copying the file data, e.g. from a data.arc memory mapped by the caller.
+    ///
+    /// Only the node section is copied out of `data`, as it may need to be decompressed.
+    /// Nothing is kept to read file data from, so `get_file` will return `GetFileError::MetadataOnly` the same as `DataArc::new_metadata_only`.
+    pub fn parse_bytes(data: &[u8]) -> Result<DataArc, ParseError> {
+        DataArc::parse_reader(&mut Cursor::new(data), &ParseOptions::new(), None)
+    }
+
+    /// Parse the data.arc read by `reader`, keeping it to read file data from, e.g. a `Cursor` or a file of a virtual filesystem.
+    pub fn from_reader<R: Read + Seek + Send + Sync + 'static>(reader: R) -> Result<DataArc, ParseError> {
+        DataArc::from_reader_with_options(reader, ParseOptions::new())
+    }
+
+    /// The same as `DataArc::from_reader` but configured by the passed `ParseOptions`.
+    ///
+    /// `ParseOptions::mmap` is ignored, as only a `File` can be memory mapped.
+    pub fn from_reader_with_options<R: Read + Seek + Send + Sync + 'static>(reader: R, options: ParseOptions) -> Result<DataArc, ParseError> {
+        // The metadata is read with many small reads, buffer them in case the reader is slow to read from.
+        let mut reader = BufReader::new(reader);
+        let data_arc = DataArc::parse_reader(&mut reader, &options, None)?;
+        let decompressor = match options.decompressor {
+            Some(decompressor) => Arc::from(decompressor),
+            None => data_arc.decompressor,
+        };
+        Ok(DataArc { backend: Some(Backend::Reader(Box::new(reader.into_inner()))), decompressor, ..data_arc })
+    }
+
+    /// Open and parse the data.arc at `path`, the same as `DataArc::new(File::open(path)?)`.
+    pub fn parse_file(path: &Path) -> Result<DataArc, ParseError> {
+        DataArc::new(File::open(path)?)
+    }
+
+    /// Keep `file` to read file data from and use the decompressor, as configured by `options`.
+    fn with_backend(self, file: File, options: ParseOptions) -> Result<DataArc, ParseError> {
+        let decompressor = match options.decompressor {
+            Some(decompressor) => Arc::from(decompressor),
+            None => self.decompressor,
+        };
+
+        #[cfg(feature = "mmap")]
+        {
+            if options.mmap {
+                // Safety: The caller of ParseOptions::mmap has promised not to modify the file while it is mapped.
+                let mmap = unsafe { memmap2::Mmap::map(&file)? };
+                return Ok(DataArc { backend: Some(Backend::Mmap(mmap)), decompressor, ..self });
             }
-        } else {
-            return Err(ParseError::NotDataArc);
         }
 
-        DataArc::internal_new(file).map_err(|err| ParseError::InternalError(err))
+        Ok(DataArc { backend: Some(Backend::File(file)), decompressor, ..self })
     }
 
-    pub fn internal_new(mut file: File) -> Result<DataArc, Error> {
-        let mut buffer = vec!(0; ARC_HEADER_SIZE);
-        file.read_exact(&mut buffer)?;
-        let header: ArcHeader = buffer.pread_with(0, LE)?;
+    /// When `warnings` is `Some`, sections that fail to decode are pushed to it instead of returning an error.
+    fn parse(file: File, options: &ParseOptions, warnings: Option<&mut Vec<ParseWarning>>) -> Result<(DataArc, File), ParseError> {
+        // The metadata is read with many small reads, buffer them to reduce the number of syscalls.
+        let mut reader = BufReader::new(file);
+        let data_arc = DataArc::parse_reader(&mut reader, options, warnings)?;
+        Ok((data_arc, reader.into_inner()))
+    }
 
-        file.seek(SeekFrom::Start(header.node_section_offset))?;
+    /// Parse the data.arc read by `reader`, without keeping a backend to read file data from.
+    fn parse_reader<R: Read + Seek>(reader: &mut R, options: &ParseOptions, warnings: Option<&mut Vec<ParseWarning>>) -> Result<DataArc, ParseError> {
+        reader.seek(SeekFrom::Start(options.magic_offset))?;
+        let header = read_arc_header(reader, options.expected_magic)?;
+        DataArc::internal_new(reader, header, options, warnings)
+    }
+
+    fn internal_new<R: Read + Seek>(file: &mut R, header: ArcHeader, options: &ParseOptions, mut warnings: Option<&mut Vec<ParseWarning>>) -> Result<DataArc, ParseError> {
+        let base_offset = options.magic_offset;
+        let file_len = file.seek(SeekFrom::End(0))?;
+
+        file.seek(SeekFrom::Start(base_offset + header.node_section_offset))?;
 
         let mut buffer = vec!(0; COMPRESSED_NODE_HEADER_SIZE);
         file.read_exact(&mut buffer)?;
         let compressed: CompressedNodeHeader = buffer.pread_with(0, LE)?;
+        let remaining = file_len.saturating_sub(base_offset + header.node_section_offset);
+
+        // An uncompressed node section starts with the NodeHeader, whose first field is the size of the entire node section.
+        // A compressed node section starts with a CompressedNodeHeader instead, whose first field is the offset to the zstd data.
+        // The offset is always small so we can tell them apart.
+        let (node, stored_size, format) = if compressed.data_start == 0 {
+            // Taken as a compressed node section the zstd data would overlap the header, so this is a NodeHeader with a size of 0.
+            return Err(ParseError::InvalidNodeSize { size: 0, remaining });
+        } else if compressed.data_start < 0x100 {
+            if (compressed.decomp_size as usize) < NODE_HEADER_SIZE {
+                return Err(ParseError::InvalidNodeSize { size: compressed.decomp_size as u64, remaining });
+            }
+            check_node_limit(compressed.decomp_size as u64, options.max_node_size)?;
+            // Check the zstd data fits before allocating a buffer for it, the size may be garbage in a corrupt data.arc
+            let stored_size = compressed.data_start as u64 + compressed.zstd_comp_size as u64;
+            if stored_size > remaining {
+                return Err(ParseError::InvalidNodeSize { size: stored_size, remaining });
+            }
+            file.seek(SeekFrom::Start(base_offset + header.node_section_offset + compressed.data_start as u64))?;
+            let mut buffer_comp = vec!(0; compressed.zstd_comp_size as usize);
+            file.read_exact(&mut buffer_comp)?;
 
-        let (node_header, buffer) = if compressed.data_start < 0x100 {
-            // TODO: Handle compressed node
-            unimplemented!()
+            let mut node = vec!(0; compressed.decomp_size as usize);
+            let bytes_copied = zstd::block::decompress_to_buffer(&buffer_comp, &mut node)
+                .map_err(|error| ParseError::DecompressFailed { decomp_size: compressed.decomp_size as usize, error })?;
+            if bytes_copied != compressed.decomp_size as usize {
+                return Err(ParseError::DecompressSizeMismatch { expected: compressed.decomp_size as usize, actual: bytes_copied });
+            }
+            let format = NodeFormat::Compressed { comp_size: compressed.zstd_comp_size, decomp_size: compressed.decomp_size };
+            (node, stored_size, format)
         } else {
-            file.seek(SeekFrom::Start(header.node_section_offset))?;
-            let mut buffer = vec!(0; NODE_HEADER_SIZE);
-            file.read_exact(&mut buffer)?;
-            let node_header: NodeHeader = buffer.pread_with(0, LE)?;
-
-            let mut buffer = vec!(0; node_header.file_size as usize - NODE_HEADER_SIZE);
-            file.read_exact(&mut buffer)?;
-            (node_header, buffer)
+            let size = compressed.data_start as u64;
+            if size < NODE_HEADER_SIZE as u64 || size > remaining {
+                return Err(ParseError::InvalidNodeSize { size, remaining });
+            }
+            check_node_limit(size, options.max_node_size)?;
+            file.seek(SeekFrom::Start(base_offset + header.node_section_offset))?;
+            let mut node = vec!(0; compressed.data_start as usize);
+            file.read_exact(&mut node)?;
+            (node, size, NodeFormat::Uncompressed { size: compressed.data_start })
+        };
+
+        if let Err(error) = check_node_overlap(&header, stored_size) {
+            match warnings.as_mut() {
+                Some(warnings) => warnings.push(ParseWarning { section: "node", error }),
+                None => return Err(error),
+            }
+        }
+
+        if options.check_version && is_pre_300_layout(&node) {
+            return Err(ParseError::UnsupportedVersion { detected: ArcVersion::Pre300 });
+        }
+
+        let strict = warnings.is_none();
+        let node_section = NodeSection {
+            offset: base_offset + header.node_section_offset,
+            size: stored_size,
+            compressed: compressed.data_start < 0x100,
+            format,
         };
+        let mut data_arc = DataArc::decode_node_section(header, file_len, base_offset, options.max_entries_per_section, node, warnings)?;
+        data_arc.node_section = node_section;
+        data_arc.magic = options.expected_magic;
+        #[cfg(feature = "digest")]
+        {
+            let mut raw_header = vec!(0; ARC_HEADER_SIZE);
+            file.seek(SeekFrom::Start(base_offset + 8))?;
+            file.read_exact(&mut raw_header)?;
+            data_arc.raw_headers.splice(0..0, raw_header);
+        }
+        data_arc.apply_options(options, strict)?;
+
+        log::debug!("{:x?}", data_arc.header);
+        log::debug!("{:x?}", data_arc.node_header);
+        if log::log_enabled!(log::Level::Trace) {
+            for (name, entry) in data_arc.first_entries() {
+                log::trace!("{}: {}", name, entry);
+            }
+        }
+        Ok(data_arc)
+    }
+
+    /// Decode the sections and run the checks that `options` asks for on a newly decoded `DataArc`.
+    /// The checks are only run when `strict`, i.e. not for `DataArc::new_lenient`.
+    fn apply_options(&mut self, options: &ParseOptions, strict: bool) -> Result<(), ParseError> {
+        if !options.lazy {
+            self.decode_sections();
+        }
+        if options.check_alignment && strict {
+            self.check_alignment().map_err(ParseError::Misaligned)?;
+        }
+        if options.validate_indices && strict {
+            self.validate_indices().map_err(ParseError::InvalidIndices)?;
+        }
+        if options.check_node_size && strict {
+            let expected = known_node_end(self.numbers, self.node_header.file_lookup_count, self.buffer.len());
+            let actual = self.buffer.iter().rposition(|&x| x != 0).map_or(0, |i| i + 1);
+            if actual > expected {
+                return Err(ParseError::SizeMismatch { expected, actual });
+            }
+        }
+        if options.check_version && strict {
+            let detected = self.version();
+            if detected != ArcVersion::Known {
+                return Err(ParseError::UnsupportedVersion { detected });
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the uncompressed contents of the node section, this is the same whether or not it was stored compressed.
+    fn decode_node_section(
+        header: ArcHeader,
+        file_len: u64,
+        base_offset: u64,
+        max_entries: u64,
+        mut buffer: Vec<u8>,
+        mut warnings: Option<&mut Vec<ParseWarning>>,
+    ) -> Result<DataArc, ParseError> {
+        let node_header: NodeHeader = buffer.pread_with(0, LE)?;
+        #[cfg(feature = "digest")]
+        let raw_headers = buffer[..NODE_HEADER_SIZE].to_vec();
+        buffer.drain(..NODE_HEADER_SIZE);
+
+        for &(count_name, count) in &node_header.counts() {
+            check_entry_count(count_name, count, max_entries)?;
+        }
+
+        let SectionOffsets {
+            bulkfile_hash_lookup, bulkfiles_by_name, bulkfile_lookup_to_fileidx, file_pairs, another_hash_table, big_hashes, big_files,
+            folder_hash_lookup, trees, sub_files1, sub_files2, folder_to_big_hash, file_lookup_buckets,
+        } = node_header.section_offsets();
+        let fits = check_sections_fit(&mut warnings, buffer.len(), &[
+            ("bulkfile_category_info", bulkfile_hash_lookup),
+            ("bulkfile_hash_lookup", bulkfiles_by_name),
+            ("bulkfiles_by_name", bulkfile_lookup_to_fileidx),
+            ("bulkfile_lookup_to_fileidx", file_pairs),
+            ("file_pairs", another_hash_table),
+            ("another_hash_table", big_hashes),
+            ("big_hashes", big_files),
+            ("big_files", folder_hash_lookup),
+            ("folder_hash_lookup", trees),
+            ("trees", sub_files1),
+            ("sub_files1", sub_files2),
+            ("sub_files2", folder_to_big_hash),
+            ("folder_to_big_hash", file_lookup_buckets),
+        ])?;
+        // A first bucket that doesn't fit is reported by check_sections_fit, as file_lookup starts after it
+        let first_hash_bucket: HashBucket = buffer.pread_with(file_lookup_buckets, LE).unwrap_or_default();
+        check_entry_count("file_lookup_buckets", first_hash_bucket.num_entries, max_entries)?;
+        let file_lookup = section_end(file_lookup_buckets, HASH_BUCKET_SIZE, first_hash_bucket.num_entries.saturating_add(1));
+        let numbers = section_end(file_lookup, ENTRY_PAIR_SIZE, node_header.file_lookup_count);
+        // The size of the numbers section is not known, so it can't be checked
+        if fits {
+            check_sections_fit(&mut warnings, buffer.len(), &[
+                ("file_lookup_buckets", file_lookup),
+                ("file_lookup", numbers),
+            ])?;
+        }
 
-        // The node_header tells us how many entries are in each section.
-        // From this we know the end of each section and thus the start of the next section.
-        let bulkfile_hash_lookup = ENTRY_TRIPLET_SIZE * node_header.movie_count as usize;
-        let bulkfiles_by_name = bulkfile_hash_lookup + ENTRY_PAIR_SIZE * node_header.part1_count as usize;
-        let bulkfile_lookup_to_fileidx = bulkfiles_by_name + ENTRY_TRIPLET_SIZE * node_header.part1_count as usize;
-        let file_pairs = bulkfile_lookup_to_fileidx + 4 * node_header.part2_count as usize;
-        let another_hash_table = file_pairs + FILE_PAIR_SIZE * node_header.music_file_count as usize;
-        let big_hashes = another_hash_table + ENTRY_TRIPLET_SIZE * node_header.another_hash_table_size as usize;
-        let big_files = big_hashes + BIG_HASH_ENTRY_SIZE * node_header.folder_count as usize;
-        let folder_hash_lookup = big_files + BIG_FILE_ENTRY_SIZE * (node_header.file_count1 + node_header.file_count2) as usize;
-        let trees = folder_hash_lookup + ENTRY_PAIR_SIZE * node_header.hash_folder_count as usize;
-        let sub_files1 = trees + TREE_ENTRY_SIZE * node_header.tree_count as usize;
-        let sub_files2 = sub_files1 + FILE_ENTRY_SIZE * node_header.sub_files1_count as usize;
-        let folder_to_big_hash = sub_files2 + FILE_ENTRY_SIZE * node_header.sub_files2_count as usize;
-        let file_lookup_buckets = folder_to_big_hash + ENTRY_PAIR_SIZE * node_header.folder_count as usize;
-        let first_hash_bucket: HashBucket = (&buffer[file_lookup_buckets..]).pread_with(0, LE)?;
-        let file_lookup = file_lookup_buckets + HASH_BUCKET_SIZE * (first_hash_bucket.num_entries as usize + 1);
-        let numbers = file_lookup + ENTRY_PAIR_SIZE * node_header.file_lookup_count as usize;
+        // Only the bounds of these sections are checked here, their entries are decoded when first accessed.
+        // A section that doesn't fit was already reported by check_sections_fit, so it is left empty without another warning.
+        let bulkfile_file_indices = LazySection::new(
+            section_range(&buffer, "bulkfile_lookup_to_fileidx", bulkfile_lookup_to_fileidx, file_pairs).unwrap_or_default(),
+            4, LittleEndian::read_u32,
+        );
+        let music_files = LazySection::new(
+            section_range(&buffer, "file_pairs", file_pairs, another_hash_table).unwrap_or_default(),
+            FILE_PAIR_SIZE, read_file_pair,
+        );
+        let another_hash_entries = LazySection::new(
+            section_range(&buffer, "another_hash_table", another_hash_table, big_hashes).unwrap_or_default(),
+            ENTRY_TRIPLET_SIZE, read_triplet,
+        );
+        if let Some(warnings) = warnings.as_mut() {
+            // The sections after another_hash_table are found from its size, so if the size is wrong they will be misaligned.
+            // Every folder_to_big_hash entry points at the big_hashes entry of the same folder so check the first one still does.
+            if let Some(error) = check_big_hash_alignment(&buffer, big_hashes, folder_to_big_hash, node_header.folder_count) {
+                warnings.push(ParseWarning { section: "another_hash_table", error: error.into() });
+            }
+            // Non zero bytes after the last known section are probably a section that isn't known yet
+            let trailing = &buffer[known_node_end(numbers, node_header.file_lookup_count, buffer.len())..];
+            let nonzero = trailing.iter().filter(|&&x| x != 0).count();
+            if nonzero != 0 {
+                let error = format_err!("{:#x} of the {:#x} bytes after the numbers section are not zero, they may be an unknown section", nonzero, trailing.len());
+                warnings.push(ParseWarning { section: "node", error: error.into() });
+            }
+        }
+        let tree_entries = LazySection::new(
+            section_range(&buffer, "trees", trees, sub_files1).unwrap_or_default(),
+            TREE_ENTRY_SIZE, read_tree_entry,
+        );
+        let file_entries = LazySection::new(
+            section_range(&buffer, "sub_files", sub_files1, folder_to_big_hash).unwrap_or_default(),
+            FILE_ENTRY_SIZE, read_file_entry,
+        );
 
         Ok(DataArc {
-            file,
+            backend: None,
+            file_len,
+            base_offset,
             header,
+            node_header,
             buffer,
 
             // offsets into the buffer taken derived from NodeSection
@@ -133,10 +913,208 @@ impl DataArc {
             numbers,
 
             first_hash_bucket,
+            bulkfile_file_indices,
+            music_files,
+            another_hash_entries,
+            tree_entries,
+            file_entries,
+
+            labels: None,
+            node_section: NodeSection::default(),
+            magic: DATA_ARC_MAGIC,
+            decompressor: Arc::new(ZstdDecompressor),
+
+            #[cfg(feature = "tokio")]
+            async_file: tokio::sync::Mutex::new(None),
+            #[cfg(feature = "digest")]
+            raw_headers,
         })
     }
 
+    /// Get the data of a file, decompressed.
+    ///
+    /// When the path has more than one tree entry, e.g. regional variants, the one found through the `file_lookup` section is read.
+    /// A variant can't be chosen by region since what marks the region of a file is not known, see `FileEntry::flags`.
     pub fn get_file(&mut self, file_name: &str) -> Result<Vec<u8>, GetFileError> {
+        let location = self.locate(file_name)?;
+        check_compression(&location.sub_file)?;
+        let buffer_comp = self.read_stored(&location)?;
+        decompress(&*self.decompressor, &location.sub_file, buffer_comp)
+    }
+
+    /// The same as `get_file` but the file is found by the hash40 of its path, e.g. for a file whose path has no label.
+    ///
+    /// Fails with `GetFileError::FileNotFound` when the hash is not in the `file_lookup` section.
+    pub fn get_file_by_hash(&mut self, hash: u64) -> Result<Vec<u8>, GetFileError> {
+        let location = self.locate_hash(hash)?;
+        check_compression(&location.sub_file)?;
+        let buffer_comp = self.read_stored(&location)?;
+        decompress(&*self.decompressor, &location.sub_file, buffer_comp)
+    }
+
+    /// Get the data of a file exactly as it is stored in the data.arc, without decompressing it.
+    /// Useful for copying a file between data.arcs without changing its bytes.
+    pub fn get_file_raw(&mut self, file_name: &str) -> Result<Vec<u8>, GetFileError> {
+        let location = self.locate(file_name)?;
+        self.read_stored(&location)
+    }
+
+    /// Get the data of a file both as it is stored in the data.arc and decompressed, reading it only once.
+    pub fn read_file_full(&mut self, file_name: &str) -> Result<FileData, GetFileError> {
+        let location = self.locate(file_name)?;
+        let raw = self.read_stored(&location)?;
+        if location.sub_file.suboffset_decompressed() {
+            return Ok(FileData { decompressed: raw.clone(), raw, compressed: false });
+        }
+        let decompressed = decompress(&*self.decompressor, &location.sub_file, raw.clone())?;
+        Ok(FileData { raw, decompressed, compressed: true })
+    }
+
+    /// Get the first `n` bytes of a file, e.g. to check the magic number of the file.
+    ///
+    /// Compressed files are only decompressed until `n` bytes are produced, so this is much faster than `get_file` for a large file.
+    /// Returns the entire file when it is shorter than `n` bytes.
+    /// Compressed files are always decompressed with the `zstd` crate, ignoring `ParseOptions::decompressor`.
+    pub fn peek_file(&mut self, file_name: &str, n: usize) -> Result<Vec<u8>, GetFileError> {
+        let location = self.locate(file_name)?;
+        let sub_file = location.sub_file;
+        if sub_file.suboffset_decompressed() {
+            return self.read_range(location.offset, (n as u64).min(sub_file.comp_size as u64));
+        }
+        check_compression(&sub_file)?;
+
+        let n = n.min(sub_file.decomp_size as usize) as u64;
+        let mut buffer = vec!();
+        match self.backend.as_mut().ok_or(GetFileError::MetadataOnly)? {
+            Backend::File(file) => {
+                file.seek(SeekFrom::Start(location.offset))?;
+                let decoder = zstd::stream::Decoder::new(file.take(sub_file.comp_size as u64))?;
+                decoder.take(n).read_to_end(&mut buffer)?;
+            }
+            #[cfg(feature = "mmap")]
+            Backend::Mmap(mmap) => {
+                let decoder = zstd::stream::Decoder::new(&mmap[location.range()])?;
+                decoder.take(n).read_to_end(&mut buffer)?;
+            }
+            Backend::Memory(data) => {
+                let decoder = zstd::stream::Decoder::new(&data[location.range()])?;
+                decoder.take(n).read_to_end(&mut buffer)?;
+            }
+            Backend::Reader(reader) => {
+                reader.seek(SeekFrom::Start(location.offset))?;
+                let decoder = zstd::stream::Decoder::new(reader.take(sub_file.comp_size as u64))?;
+                decoder.take(n).read_to_end(&mut buffer)?;
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Seek the file to the start of the data of a file, so it can be read without the `DataArc`.
+    ///
+    /// Returns the size of the data as stored in the data.arc and if it is compressed.
+    /// This moves the position of the `File` the `DataArc` was created from,
+    /// which is shared with any `File` cloned from it with `File::try_clone` before it was passed to the `DataArc`,
+    /// so the clone can then read the data from its current position.
+    /// Other methods of the `DataArc` seek the file as well, so the data should be read before calling them.
+    /// Fails when the `DataArc` memory maps the file or holds the data.arc in memory, as there is no position to move.
+    pub fn seek_to_file(&mut self, file_name: &str) -> Result<(u64, bool), GetFileError> {
+        let location = self.locate(file_name)?;
+        match self.backend.as_mut().ok_or(GetFileError::MetadataOnly)? {
+            Backend::File(file) => {
+                file.seek(SeekFrom::Start(location.offset))?;
+            }
+            #[cfg(feature = "mmap")]
+            Backend::Mmap(_) => {
+                return Err(format_err!("Failed to seek to {}: The data.arc is memory mapped", file_name).into());
+            }
+            Backend::Memory(_) => {
+                return Err(format_err!("Failed to seek to {}: The data.arc is held in memory", file_name).into());
+            }
+            Backend::Reader(_) => {
+                return Err(format_err!("Failed to seek to {}: The data.arc is read from a reader owned by the DataArc", file_name).into());
+            }
+        }
+        Ok((location.sub_file.comp_size as u64, !location.sub_file.suboffset_decompressed()))
+    }
+
+    /// Read the `comp_size` bytes stored at `location`.
+    fn read_stored(&mut self, location: &FileLocation) -> Result<Vec<u8>, GetFileError> {
+        self.read_range(location.offset, location.sub_file.comp_size as u64)
+    }
+
+    /// Read `size` bytes at the absolute `offset` in the data.arc.
+    fn read_range(&mut self, offset: u64, size: u64) -> Result<Vec<u8>, GetFileError> {
+        if offset.checked_add(size).map(|end| end > self.file_len).unwrap_or(true) {
+            return Err(GetFileError::SectionOverrun { offset, size, file_len: self.file_len });
+        }
+
+        match self.backend.as_mut().ok_or(GetFileError::MetadataOnly)? {
+            Backend::File(file) => {
+                let mut buffer = vec!(0; size as usize);
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut buffer)?;
+                Ok(buffer)
+            }
+            #[cfg(feature = "mmap")]
+            Backend::Mmap(mmap) => Ok(mmap[offset as usize .. (offset + size) as usize].to_vec()),
+            Backend::Memory(data) => Ok(data[offset as usize .. (offset + size) as usize].to_vec()),
+            Backend::Reader(reader) => {
+                let mut buffer = vec!(0; size as usize);
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.read_exact(&mut buffer)?;
+                Ok(buffer)
+            }
+        }
+    }
+
+    /// Read the first `len` bytes of the section at `unk_section_offset`.
+    ///
+    /// Nothing is known about the structure of this section yet, this is provided so it can be investigated.
+    pub fn unknown_section_bytes(&mut self, len: usize) -> Result<Vec<u8>, GetFileError> {
+        let size = len as u64;
+        let offset = self.base_offset.checked_add(self.header.unk_section_offset)
+            .ok_or(GetFileError::SectionOverrun { offset: u64::MAX, size, file_len: self.file_len })?;
+        self.read_range(offset, size)
+    }
+
+    /// The same as `get_file` but the file is read with tokio and decompressed on tokio's blocking thread pool.
+    /// Must be called from within a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn get_file_async(&self, file_name: &str) -> Result<Vec<u8>, GetFileError> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let location = self.locate(file_name)?;
+        check_compression(&location.sub_file)?;
+
+        let buffer_comp = match self.backend.as_ref().ok_or(GetFileError::MetadataOnly)? {
+            Backend::File(file) => {
+                // The cloned handle shares its file cursor, so concurrent reads are serialized by the lock.
+                let mut async_file = self.async_file.lock().await;
+                if async_file.is_none() {
+                    *async_file = Some(tokio::fs::File::from_std(file.try_clone()?));
+                }
+                let file = async_file.as_mut().unwrap();
+                let mut buffer_comp = vec!(0; location.sub_file.comp_size as usize);
+                file.seek(SeekFrom::Start(location.offset)).await?;
+                file.read_exact(&mut buffer_comp).await?;
+                buffer_comp
+            }
+            #[cfg(feature = "mmap")]
+            Backend::Mmap(mmap) => mmap[location.range()].to_vec(),
+            Backend::Memory(data) => data[location.range()].to_vec(),
+            Backend::Reader(_) => {
+                return Err(format_err!("Failed to read {}: A data.arc parsed with DataArc::from_reader can't be read asynchronously", file_name).into());
+            }
+        };
+
+        let sub_file = location.sub_file;
+        let decompressor = self.decompressor.clone();
+        tokio::task::spawn_blocking(move || decompress(&*decompressor, &sub_file, buffer_comp)).await
+            .map_err(|x| GetFileError::InternalError(x.into()))?
+    }
+
+    /// Find where the data of `file_name` is stored.
+    fn locate(&self, file_name: &str) -> Result<FileLocation, GetFileError> {
         // Actual file data is stored at self.header.file_section_offset, however this contains no file metadata.
         // The metadata is stored at `self.header.node_section_offset`.
         // However the metadata doesnt actually contain filenames etc. it only contains hashes of this data.
@@ -155,100 +1133,1532 @@ impl DataArc {
         // The values at that index are sorted by hash so we use a binary search on the hash to find the correct `EntryPair`.
         // And finally an offset to `self.trees` is in `self.file_lookup`.
 
-        let hash = hash40(file_name);
+        self.locate_hash(hash40(file_name))
+    }
+
+    /// Find where the data of the file whose path has the hash40 `hash` is stored, see `locate`.
+    fn locate_hash(&self, hash: u64) -> Result<FileLocation, GetFileError> {
+        let index = self.lookup_tree_index(hash)?;
+        let tree = self.tree_entries().get(index)
+            .ok_or_else(|| format_err!("Failed to locate {:#x}: Tree index out of range", hash))?;
+        self.locate_tree(tree)
+    }
+
+    /// Find the index in the `trees` section of the path with the hash40 `hash` with the `file_lookup` buckets.
+    fn lookup_tree_index(&self, hash: u64) -> Result<usize, GetFileError> {
         let num_buckets = self.first_hash_bucket.num_entries;
+        if num_buckets == 0 {
+            return Err(GetFileError::FileNotFound);
+        }
         let offset = self.file_lookup_buckets + HASH_BUCKET_SIZE * (hash % num_buckets as u64 + 1) as usize;
-        let bucket: HashBucket = self.buffer[offset..].pread_with(0, LE).map_err(|x: ScrollError| GetFileError::InternalError(x.into()))?;
+        let bucket: HashBucket = self.buffer.get(offset..).unwrap_or(&[]).pread_with(0, LE)?;
         let entry = self.bucket_search(hash, &bucket)?;
-        let tree = read_tree_entry(&self.buffer[self.trees + TREE_ENTRY_SIZE * entry.meta as usize..]);
-        // TODO: Hmmm I wonder if I'm supposed to further check the individual checksums of the tree
+        Ok(entry.meta as usize)
+    }
 
-        if tree.redirect() {
-            unimplemented!();
-        }
+    /// Find where the data of the file described by `tree` is stored.
+    fn locate_tree(&self, tree: &TreeEntry) -> Result<FileLocation, GetFileError> {
+        let index = self.resolve_file_entry(tree)?;
+        let sub_file = self.file_entries()[index];
 
-        let suboffset_index = if tree.suboffset_index() {
-            tree.suboffset_index as usize
-        } else {
-            let file_entry: FileEntry = (&self.buffer[self.sub_files1 + FILE_ENTRY_SIZE * tree.ext.meta as usize..])
-                .pread_with(0, LE).map_err(|x: ScrollError| GetFileError::InternalError(x.into()))?;
-            if file_entry.suboffset_redir() {
-                tree.ext.meta as usize + file_entry.suboffset_tree_index()
-            } else {
-                tree.ext.meta as usize
+        let big_hash = self.big_hash_entry(tree.path.meta as usize)
+            .ok_or_else(|| format_err!("Failed to locate the big hash {} of the tree entry with path {:#x}", tree.path.meta, tree.path.hash))?;
+        let big_file: BigFileEntry = self.buffer.get(self.big_files + BIG_FILE_ENTRY_SIZE * big_hash.path.meta as usize ..).unwrap_or(&[]).pread_with(0, LE)?;
+
+        self.locate_in_big_file(&big_file, index, sub_file)
+    }
+
+    /// Find where the data of `sub_file`, the `FileEntry` at `index` and a member of `big_file`, is stored.
+    fn locate_in_big_file(&self, big_file: &BigFileEntry, index: usize, sub_file: FileEntry) -> Result<FileLocation, GetFileError> {
+        let size = sub_file.comp_size as u64;
+        match self.file_data_offset(big_file, &sub_file) {
+            Some(offset) if offset.checked_add(size).map(|end| end <= self.file_len).unwrap_or(false) => {
+                Ok(FileLocation { offset, index, sub_file })
             }
-        };
-        let sub_file: FileEntry = (&self.buffer[self.sub_files1 + FILE_ENTRY_SIZE * suboffset_index..])
-            .pread_with(0, LE).map_err(|x: ScrollError| GetFileError::InternalError(x.into()))?;
+            offset => Err(GetFileError::SectionOverrun { offset: offset.unwrap_or(u64::MAX), size, file_len: self.file_len }),
+        }
+    }
 
-        let big_hash: BigHashEntry = read_big_hash_entry(&self.buffer[self.big_hashes + BIG_HASH_ENTRY_SIZE * tree.path.meta as usize ..]);
-        let big_file: BigFileEntry = (&self.buffer[self.big_files + BIG_FILE_ENTRY_SIZE * big_hash.path.meta as usize ..])
-            .pread_with(0, LE).map_err(|x: ScrollError| GetFileError::InternalError(x.into()))?;
+    /// The absolute offset of the data of `sub_file`, a member of `big_file`, or `None` when it overflows a u64.
+    pub(crate) fn file_data_offset(&self, big_file: &BigFileEntry, sub_file: &FileEntry) -> Option<u64> {
+        self.base_offset.checked_add(self.header.file_section_offset)?
+            .checked_add(big_file.offset)?
+            .checked_add((sub_file.offset as u64).checked_mul(4)?)
+    }
 
-        if sub_file.suboffset_decompressed() {
-            unimplemented!()
+    /// The absolute offset of the start of the file section, saturating instead of overflowing for a corrupt header.
+    pub(crate) fn file_section_start(&self) -> u64 {
+        self.base_offset.saturating_add(self.header.file_section_offset)
+    }
+
+    /// Every entry of the `big_files` section, in the order they are stored.
+    pub fn big_files(&self) -> impl Iterator<Item = BigFileEntry> + '_ {
+        let count = self.node_header.file_count1 as usize + self.node_header.file_count2 as usize;
+        (0..count).map_while(move |i| self.buffer.pread_with(self.big_files + BIG_FILE_ENTRY_SIZE * i, LE).ok())
+    }
+
+    /// Every entry of the `file_lookup` section, in the order they are stored.
+    ///
+    /// Each entry pairs the hash40 of a path with the index of its tree entry, grouped by the `file_lookup_buckets` bucket they are in.
+    pub fn file_lookup(&self) -> impl Iterator<Item = EntryPair> + '_ {
+        (0..self.node_header.file_lookup_count as usize)
+            .map_while(move |i| self.buffer.get(self.file_lookup + ENTRY_PAIR_SIZE * i..).filter(|x| x.len() >= ENTRY_PAIR_SIZE))
+            .map(read_pair)
+    }
+
+    /// Get the data of the `member`th file stored in `big`, decompressed.
+    ///
+    /// The members of a big file are the `big.files` file entries starting at `big.suboffset_index`,
+    /// so member `n` is described by the `FileEntry` at `big.suboffset_index + n`, the same index `DataArc::file_by_index` takes.
+    /// The offset of each member's `FileEntry` is relative to the offset of `big`.
+    /// Members that are stored uncompressed are returned as they are stored.
+    pub fn read_big_file_member(&mut self, big: &BigFileEntry, member: u32) -> Result<Vec<u8>, GetFileError> {
+        if member >= big.files {
+            return Err(GetFileError::MemberOutOfRange { member, files: big.files });
+        }
+        let index = big.suboffset_index as usize + member as usize;
+        let sub_file = *self.file_entries().get(index)
+            .ok_or_else(|| format_err!("Failed to read member {} of the big file at {:#x}: File entry index out of range", member, big.offset))?;
+        let location = self.locate_in_big_file(big, index, sub_file)?;
+        let buffer = self.read_stored(&location)?;
+        decompress(&*self.decompressor, &sub_file, buffer)
+    }
+
+    /// Find the `FileEntry` describing where the data of the file described by `tree` is stored.
+    ///
+    /// The `FileEntry` is always one of the `sub_files1` section at `tree.suboffset_index`.
+    /// When the low two bits of `tree.flags` are set the file shares the `FileEntry` of another file,
+    /// found from `tree.ext.meta` and possibly redirected to another `FileEntry` by bits of its `flags` that aren't known yet,
+    /// so it can't be found.
+    ///
+    /// The offset of the `FileEntry` is relative to a `BigFileEntry`,
+    /// which is found by following `tree.path.meta` into the `big_hashes` section and its `path.meta` into the `big_files` section.
+    ///
+    /// Returns `None` when the index is out of range or `tree` is a redirect or shared.
+    pub fn file_entry_for(&self, tree: &TreeEntry) -> Option<&FileEntry> {
+        self.file_entry_index(tree).map(|index| &self.file_entries()[index])
+    }
+
+    /// The index into `sub_files1` of the `FileEntry` returned by `file_entry_for`.
+    fn file_entry_index(&self, tree: &TreeEntry) -> Option<usize> {
+        self.resolve_file_entry(tree).ok()
+    }
+
+    /// The index into `sub_files1` of the `FileEntry` returned by `file_entry_for`,
+    /// failing with `GetFileError::UnsupportedRedirect` when `tree` is a redirect or shared.
+    pub(crate) fn resolve_file_entry(&self, tree: &TreeEntry) -> Result<usize, GetFileError> {
+        // TODO: Hmmm I wonder if I'm supposed to further check the individual checksums of the tree
+
+        // TODO: A shared FileEntry at `tree.ext.meta` may redirect to another FileEntry,
+        // but which bit of its `flags` marks a redirect, and where the index it redirects to is stored, is not known yet.
+        // Until it is, shared files can't be followed without possibly reading the wrong data.
+        if tree.redirect() || !tree.suboffset_index() {
+            return Err(GetFileError::UnsupportedRedirect { path: tree.path.hash, flags: tree.flags });
         }
 
-        if !sub_file.suboffset_compressed_zstd() {
-            return Err(GetFileError::InternalError(format_err!("Failed to extract {}: Unknown compression", file_name)));
+        let suboffset_index = tree.suboffset_index as usize;
+        self.sub_file(suboffset_index)?;
+        Ok(suboffset_index)
+    }
+
+    /// The `FileEntry` at `index` of the `sub_files1` section followed by the `sub_files2` section.
+    ///
+    /// Indexes `0..sub_files1_count` are the `sub_files1` section in the order they are stored,
+    /// and the indexes after them are the `sub_files2` section in the order they are stored.
+    /// So an index stays the same between parses of the same data.arc.
+    pub fn file_by_index(&self, index: usize) -> Option<&FileEntry> {
+        self.file_entries().get(index)
+    }
+
+    /// Every tree entry, in the order they are stored in the `trees` section.
+    pub fn tree_entries(&self) -> &[TreeEntry] {
+        self.tree_entries.get(&self.buffer)
+    }
+
+    /// Decode every section that is otherwise decoded when first accessed, see `ParseOptions::lazy`.
+    fn decode_sections(&self) {
+        self.bulkfile_lookup_to_fileidx();
+        self.music_files();
+        self.another_hash_table();
+        self.tree_entries();
+        self.file_entries();
+    }
+
+    /// Every file entry, the `sub_files1` section followed by the `sub_files2` section.
+    fn file_entries(&self) -> &[FileEntry] {
+        self.file_entries.get(&self.buffer)
+    }
+
+    /// Every tree entry whose path has the hash40 `hash`, in the order they are stored in the `trees` section.
+    ///
+    /// A path can have more than one tree entry, e.g. when the file is shared between folders or has regional variants.
+    pub fn lookup_all(&self, hash: u64) -> Vec<&TreeEntry> {
+        self.tree_entries().iter().filter(|tree| tree.path.hash == hash).collect()
+    }
+
+    /// Every tree entry grouped by the hash40 of its folder, sorted by the hash.
+    /// The tree entries of each folder are in the order they are stored in the `trees` section.
+    pub fn entries_by_folder(&self) -> BTreeMap<u64, Vec<&TreeEntry>> {
+        let mut folders: BTreeMap<u64, Vec<&TreeEntry>> = BTreeMap::new();
+        for tree in self.tree_entries() {
+            folders.entry(tree.folder.hash).or_default().push(tree);
         }
+        folders
+    }
 
-        let mut buffer_comp = vec!(0; sub_file.comp_size as usize);
-        self.file.seek(SeekFrom::Start(self.header.file_section_offset + big_file.offset + sub_file.offset as u64 * 4))
-            .map_err(|x: IOError| GetFileError::InternalError(x.into()))?;
-        self.file.read_exact(&mut buffer_comp)
-            .map_err(|x: IOError| GetFileError::InternalError(x.into()))?;
+    /// The tree entry whose path has the hash40 `hash`, this is the tree entry `get_file` reads.
+    ///
+    /// Only the `file_lookup` bucket of the hash is searched, so this is much faster than scanning the `trees` section,
+    /// `benches/lookup.rs` measures the difference.
+    /// Use `lookup_all` to find every tree entry of the path.
+    pub fn lookup_by_hash(&self, hash: u64) -> Option<&TreeEntry> {
+        let index = self.lookup_tree_index(hash).ok()?;
+        self.tree_entries().get(index)
+    }
+
+    /// The index in the `trees` section of the tree entry that `get_file(file_name)` reads, for use with `tree_entries`.
+    ///
+    /// Returns `None` when the file does not exist.
+    pub fn tree_index_of(&self, file_name: &str) -> Option<usize> {
+        self.tree_index_of_hash(hash40(file_name))
+    }
+
+    /// The same as `tree_index_of` but the file is found by the hash40 of its path.
+    ///
+    /// Only the `file_lookup` entries in the bucket of the hash are searched, rather than every tree entry.
+    pub fn tree_index_of_hash(&self, path_hash: u64) -> Option<usize> {
+        let index = self.lookup_tree_index(path_hash).ok()?;
+        if index < self.tree_entries().len() { Some(index) } else { None }
+    }
+
+    /// Get an entry of the `sub_files1` section.
+    fn sub_file(&self, index: usize) -> Result<&FileEntry, GetFileError> {
+        let sub_files1_count = self.node_header.sub_files1_count as usize;
+        let sub_files1 = &self.file_entries()[..sub_files1_count.min(self.file_entries().len())];
+        Ok(sub_files1.get(index).ok_or_else(|| format_err!("Sub file index {} out of range", index))?)
+    }
 
-        let mut buffer_decomp = vec!(0; sub_file.decomp_size as usize);
-        let bytes_copied = zstd::block::decompress_to_buffer(&buffer_comp, &mut buffer_decomp)
-            .map_err(|x: IOError| GetFileError::InternalError(x.into()))?;
-        if bytes_copied != sub_file.decomp_size as usize {
-            return Err(GetFileError::InternalError(format_err!("Failed to extract {}: Mismatch in expected and actual decompressed size", file_name)));
+    /// Borrow the data of a file directly from the memory mapped data.arc, without copying it.
+    ///
+    /// Returns `None` when the data.arc was not parsed with `ParseOptions::mmap`,
+    /// when the file does not exist, or when the file is compressed and so must be read with `get_file`.
+    #[cfg(feature = "mmap")]
+    pub fn mmap_slice(&self, file_name: &str) -> Option<&[u8]> {
+        let location = self.locate(file_name).ok()?;
+        if !location.sub_file.suboffset_decompressed() {
+            return None;
+        }
+        match &self.backend {
+            Some(Backend::Mmap(mmap)) => Some(&mmap[location.range()]),
+            _ => None,
         }
+    }
 
-        Ok(buffer_decomp)
+    /// Every file entry referenced by a tree entry, sorted by where its data is stored in the data.arc.
+    /// Reading files in this order turns random seeks into a near sequential read.
+    ///
+    /// Entries stored at the same offset are in the order they are found in the `trees` section.
+    /// Tree entries that cannot be located are skipped.
+    pub fn entries_by_offset(&self) -> Vec<&FileEntry> {
+        self.locations_by_offset().iter().map(|location| &self.file_entries()[location.index]).collect()
     }
 
-    /// TODO: Binary search
-    fn bucket_search(&self, hash: u64, bucket: &HashBucket) -> Result<EntryPair, GetFileError> {
-        let start_index = self.file_lookup + ENTRY_PAIR_SIZE * bucket.index as usize;
-        for i in 0..self.first_hash_bucket.num_entries as usize {
-            let pair = read_pair(&self.buffer[start_index + ENTRY_PAIR_SIZE * i ..]);
-            if pair.hash == hash {
-                return Ok(pair);
+    /// The unused byte ranges between the data of consecutive files, as `(absolute offset, length)`.
+    /// This is usually alignment padding.
+    ///
+    /// Tree entries that cannot be located are skipped.
+    pub fn storage_gaps(&self) -> Vec<(u64, u64)> {
+        let mut gaps = vec!();
+        let mut end: Option<u64> = None;
+        for location in self.locations_by_offset() {
+            if let Some(end) = end {
+                if location.offset > end {
+                    gaps.push((end, location.offset - end));
+                }
             }
+            let location_end = location.offset + location.sub_file.comp_size as u64;
+            end = Some(end.map_or(location_end, |end| end.max(location_end)));
         }
-        Err(GetFileError::FileNotFound)
+        gaps
     }
 
-    pub fn debug_print(&self) -> Result<(), Error> {
-        // TODO: print all elements
-        println!("bulkfile_category_info: {:x?}", read_triplet(&self.buffer[..]));
-        println!("bulkfile_hash_lookup: {:x?}", read_pair(&self.buffer[self.bulkfile_hash_lookup..]));
-        println!("bulkfiles_by_name: {:x?}", read_triplet(&self.buffer[self.bulkfiles_by_name..]));
-        println!("bulkfile_lookup_tofileidx: {:x?}", LittleEndian::read_u32(&self.buffer[self.bulkfile_lookup_to_fileidx..]));
-        let file_pair: FilePair = (&self.buffer[self.file_pairs..]).pread_with(0, LE)?;
-        println!("file_pairs: {:x?}", file_pair);
-        println!("another_hash_table: {:x?}", read_triplet(&self.buffer[self.another_hash_table..]));
-        println!("big_hashes: {:x?}", read_big_hash_entry(&self.buffer[self.big_hashes..]));
-        let big_file: BigFileEntry = (&self.buffer[self.big_files..]).pread_with(0, LE)?;
-        println!("big_files: {:x?}", big_file);
-        println!("folder_hash_lookup: {:x?}", read_pair(&self.buffer[self.folder_hash_lookup..]));
-        println!("trees: {:x?}", read_tree_entry(&self.buffer[self.trees..]));
-        let file_entry: FileEntry = (&self.buffer[self.sub_files1..]).pread_with(0, LE)?;
-        println!("sub_files1: {:x?}", file_entry);
-        let file_entry: FileEntry = (&self.buffer[self.sub_files2..]).pread_with(0, LE)?;
-        println!("sub_files2: {:x?}", file_entry);
-        println!("folder_to_big_hash: {:x?}", read_pair(&self.buffer[self.folder_to_big_hash..]));
-        let hash_bucket: HashBucket = (&self.buffer[self.file_lookup_buckets..]).pread_with(0, LE)?;
-        println!("file_lookup_buckets: {:x?}", hash_bucket);
-        println!("file_lookup: {:x?}", read_pair(&self.buffer[self.file_lookup..]));
-        println!("numbers: {:x?}", read_pair(&self.buffer[self.numbers..]));
+    /// The absolute byte range occupied by file data, as `(start, end)`.
+    /// Compare `end` with the length of the data.arc to find trailing data or truncation.
+    ///
+    /// Tree entries that cannot be located are skipped, returns `None` when no files can be located.
+    pub fn data_span(&self) -> Option<(u64, u64)> {
+        let locations = self.locations_by_offset();
+        let start = locations.first()?.offset;
+        let end = locations.iter().map(|location| location.offset + location.sub_file.comp_size as u64).max()?;
+        Some((start, end))
+    }
 
-        Ok(())
+    /// The absolute offset that the file data ends at, where new files can be appended.
+    ///
+    /// This is the `end` of `data_span`, or the start of the file section when no files can be located.
+    pub fn data_section_end(&self) -> u64 {
+        self.data_span().map_or(self.file_section_start(), |(_, end)| end)
     }
-}
 
-fn hash40(name: &str) -> u64 {
-    crc::crc32::checksum_ieee(name.as_bytes()) as u64 | ((name.len() as u64 & 0xFF) << 32)
+    /// Convert an absolute offset in the data.arc to an offset relative to the start of the file section,
+    /// the offset that `BigFileEntry::offset` is stored as.
+    ///
+    /// Returns `None` when `abs` is before the file section.
+    /// For any `rel` where `to_absolute_offset(rel)` is `Some(abs)`, `to_relative_offset(abs) == Some(rel)`.
+    pub fn to_relative_offset(&self, abs: u64) -> Option<u64> {
+        abs.checked_sub(self.file_section_start())
+    }
+
+    /// Convert an offset relative to the start of the file section to an absolute offset in the data.arc,
+    /// the inverse of `to_relative_offset`.
+    ///
+    /// Returns `None` when the absolute offset would overflow a `u64`, which only happens for a large `rel` or a corrupt header.
+    pub fn to_absolute_offset(&self, rel: u64) -> Option<u64> {
+        self.base_offset.checked_add(self.header.file_section_offset)?.checked_add(rel)
+    }
+
+    /// Write the bytes of the file section to `hasher`, from the start of the file section to `data_section_end`.
+    ///
+    /// The bytes are read in order in chunks of 1 MiB, rather than file by file, so the data in between files is included.
+    pub fn hash_data_region<H: Hasher>(&mut self, hasher: &mut H) -> Result<(), GetFileError> {
+        const CHUNK_SIZE: u64 = 0x100000;
+        let end = self.data_section_end();
+        let mut offset = self.file_section_start();
+        while offset < end {
+            let size = CHUNK_SIZE.min(end - offset);
+            hasher.write(&self.read_range(offset, size)?);
+            offset += size;
+        }
+        Ok(())
+    }
+
+    /// Groups of tree entries whose data is stored at the same offset, i.e. files that share their data.
+    /// Only groups of more than one tree entry are included.
+    ///
+    /// Groups are in the order their first tree entry is found in the `trees` section,
+    /// and the tree entries of each group are in the order they are found in the `trees` section.
+    /// Tree entries that cannot be located are skipped.
+    pub fn shared_groups(&self) -> Vec<Vec<&TreeEntry>> {
+        let mut indexes = HashMap::new();
+        let mut groups: Vec<Vec<&TreeEntry>> = vec!();
+        for tree in self.tree_entries() {
+            if let Ok(location) = self.locate_tree(tree) {
+                let index = *indexes.entry(location.offset).or_insert_with(|| {
+                    groups.push(vec!());
+                    groups.len() - 1
+                });
+                groups[index].push(tree);
+            }
+        }
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+
+    /// Every other tree entry whose data is stored at the same offset as the file at `file_name`,
+    /// i.e. the files that change when the data of `file_name` is changed.
+    ///
+    /// The tree entries are in the order they are found in the `trees` section, and do not include `file_name` itself.
+    /// Returns an empty `Vec` when `file_name` can't be located.
+    pub fn entries_sharing_with(&self, file_name: &str) -> Vec<&TreeEntry> {
+        let hash = hash40(file_name);
+        let offset = match self.locate(file_name) {
+            Ok(location) => location.offset,
+            Err(_) => return vec!(),
+        };
+        self.tree_entries().iter()
+            .filter(|tree| tree.path.hash != hash)
+            .filter(|tree| self.locate_tree(tree).map(|location| location.offset == offset).unwrap_or(false))
+            .collect()
+    }
+
+    /// Whether the data of `entry` is shared with another file, i.e. `entry` is used by a tree entry of one of the `shared_groups`.
+    ///
+    /// No bit of `FileEntry::flags` is known to mark a shared entry, so this is found by locating the data of every tree entry.
+    /// `entry` is compared by address, so it must be borrowed from this `DataArc`, e.g. from `file_entry_for`.
+    pub fn is_shared(&self, entry: &FileEntry) -> bool {
+        self.shared_group_of(entry).is_some()
+    }
+
+    /// The `FileEntry` of the first tree entry sharing the data of `entry`, see `is_shared`,
+    /// so every file sharing the same data resolves to the same `FileEntry`.
+    ///
+    /// Returns `entry` itself when its data isn't shared.
+    pub fn resolve_shared<'a>(&'a self, entry: &'a FileEntry) -> &'a FileEntry {
+        self.shared_group_of(entry)
+            .and_then(|group| self.file_entry_for(group[0]))
+            .unwrap_or(entry)
+    }
+
+    /// The group of `shared_groups` with a tree entry whose `FileEntry` is `entry`.
+    fn shared_group_of(&self, entry: &FileEntry) -> Option<Vec<&TreeEntry>> {
+        self.shared_groups().into_iter().find(|group| {
+            group.iter().any(|tree| self.file_entry_for(tree).is_some_and(|x| std::ptr::eq(x, entry)))
+        })
+    }
+
+    /// Groups of absolute offsets whose files have identical contents once decompressed, i.e. duplicated data that isn't shared.
+    /// Only groups of more than one offset are included.
+    ///
+    /// The data at each offset is read, decompressed and hashed once, no matter how many tree entries share it,
+    /// so this reads every file in the data.arc.
+    /// `progress` is called with the number of offsets hashed so far and the total number of offsets after each one is hashed.
+    /// Groups and the offsets in each group are sorted by offset.
+    /// Files are grouped by their size and a 64 bit hash of their contents, so files are not compared byte for byte.
+    /// Tree entries that cannot be located are skipped.
+    pub fn find_duplicate_content<F: FnMut(usize, usize)>(&mut self, mut progress: F) -> Result<Vec<Vec<u64>>, GetFileError> {
+        let mut seen = HashSet::new();
+        let mut locations: Vec<FileLocation> = self.tree_entries().iter()
+            .filter_map(|tree| self.locate_tree(tree).ok())
+            .filter(|location| seen.insert(location.offset))
+            .collect();
+        locations.sort_by_key(|location| location.offset);
+
+        let mut indexes = HashMap::new();
+        let mut groups: Vec<Vec<u64>> = vec!();
+        for (i, location) in locations.iter().enumerate() {
+            let stored = self.read_stored(location)?;
+            let data = decompress(&*self.decompressor, &location.sub_file, stored)?;
+            let mut hasher = DefaultHasher::new();
+            data.hash(&mut hasher);
+            let index = *indexes.entry((data.len(), hasher.finish())).or_insert_with(|| {
+                groups.push(vec!());
+                groups.len() - 1
+            });
+            groups[index].push(location.offset);
+            progress(i + 1, locations.len());
+        }
+        groups.retain(|group| group.len() > 1);
+        Ok(groups)
+    }
+
+    /// Compare the contents of every file found in both this data.arc and `other` by the hash40 of its path,
+    /// e.g. to find the files a game update really changed.
+    ///
+    /// Only files that changed are included, in the order they are first found in the `trees` section of this data.arc.
+    /// Files with a different size once decompressed are `Modified` without being read.
+    /// Otherwise both are read, and only decompressed when they are stored differently.
+    /// `progress` is called with the number of files compared so far and the total number of files after each one is compared.
+    /// Files found in only one of the data.arcs, or that cannot be located, are skipped.
+    pub fn content_diff<F: FnMut(usize, usize)>(&mut self, other: &mut DataArc, mut progress: F) -> Result<Vec<ContentChange>, GetFileError> {
+        let mut seen = HashSet::new();
+        let pairs: Vec<(u64, FileLocation, FileLocation)> = self.tree_entries().iter()
+            .filter(|tree| seen.insert(tree.path.hash))
+            .filter_map(|tree| {
+                let location = self.locate_tree(tree).ok()?;
+                let other_tree = other.lookup_by_hash(tree.path.hash)?;
+                Some((tree.path.hash, location, other.locate_tree(other_tree).ok()?))
+            })
+            .collect();
+
+        let mut changes = vec!();
+        for (i, (path_hash, location, other_location)) in pairs.iter().enumerate() {
+            let same = if location.sub_file.decomp_size != other_location.sub_file.decomp_size {
+                false
+            } else {
+                let stored = self.read_stored(location)?;
+                let other_stored = other.read_stored(other_location)?;
+                if location.sub_file.flags == other_location.sub_file.flags && stored == other_stored {
+                    true
+                } else {
+                    decompress(&*self.decompressor, &location.sub_file, stored)?
+                        == decompress(&*other.decompressor, &other_location.sub_file, other_stored)?
+                }
+            };
+            if !same {
+                changes.push(ContentChange { path_hash: *path_hash, kind: ContentChangeKind::Modified });
+            } else if location.offset != other_location.offset {
+                changes.push(ContentChange { path_hash: *path_hash, kind: ContentChangeKind::Moved });
+            }
+            progress(i + 1, pairs.len());
+        }
+        Ok(changes)
+    }
+
+    /// The location of every file entry referenced by a tree entry, sorted by offset.
+    fn locations_by_offset(&self) -> Vec<FileLocation> {
+        let mut seen = HashSet::new();
+        let mut locations: Vec<FileLocation> = self.tree_entries().iter()
+            .filter_map(|tree| self.locate_tree(tree).ok())
+            .filter(|location| seen.insert(location.index))
+            .collect();
+        locations.sort_by_key(|location| location.offset);
+        locations
+    }
+
+    /// The hash40 of the path of every file, grouped by how its data is compressed.
+    /// The hashes of each group are in the order they are stored in the `trees` section.
+    ///
+    /// A path is listed once for each of its tree entries. Tree entries whose `FileEntry` can't be found are skipped.
+    pub fn files_by_compression(&self) -> HashMap<Compression, Vec<u64>> {
+        let mut groups: HashMap<Compression, Vec<u64>> = HashMap::new();
+        for tree in self.tree_entries() {
+            if let Some(file_entry) = self.file_entry_for(tree) {
+                groups.entry(file_entry.compression()).or_default().push(tree.path.hash);
+            }
+        }
+        groups
+    }
+
+    /// Sum the sizes of every entry in the `sub_files1` and `sub_files2` sections.
+    pub fn compression_stats(&self) -> CompressionStats {
+        let mut total_comp = 0;
+        let mut total_decomp = 0;
+        let mut uncompressed_file_count = 0;
+        for file_entry in self.file_entries() {
+            total_comp += file_entry.comp_size as u64;
+            total_decomp += file_entry.decomp_size as u64;
+            if file_entry.suboffset_decompressed() {
+                uncompressed_file_count += 1;
+            }
+        }
+        let ratio = if total_comp == 0 { 1.0 } else { total_decomp as f64 / total_comp as f64 };
+        CompressionStats { total_comp, total_decomp, ratio, uncompressed_file_count }
+    }
+
+    /// Sum the sizes of the files in each folder, keyed by the hash40 of the folder.
+    ///
+    /// Only the tree entries directly in a folder are counted, not those of its subfolders.
+    /// A file shared between tree entries is counted once for each of them.
+    /// Redirects and tree entries without a `FileEntry` are skipped.
+    pub fn folder_stats(&self) -> HashMap<u64, FolderStats> {
+        let mut folders: HashMap<u64, FolderStats> = HashMap::new();
+        for tree in self.tree_entries() {
+            if let Some(file_entry) = self.file_entry_for(tree) {
+                let stats = folders.entry(tree.folder.hash).or_default();
+                stats.file_count += 1;
+                stats.total_decomp += file_entry.decomp_size as u64;
+                stats.total_comp += file_entry.comp_size as u64;
+            }
+        }
+        folders
+    }
+
+    /// The entries of the `bulkfile_category_info` section, the first section of the node section, in the order they are stored.
+    /// There are `movie_count` entries.
+    pub fn movie_categories(&self) -> Vec<MovieCategory> {
+        self.buffer.get(..self.bulkfile_hash_lookup).unwrap_or(&[])
+            .chunks_exact(ENTRY_TRIPLET_SIZE)
+            .map(read_triplet)
+            .map(|triplet| MovieCategory { hash: triplet.hash, start: triplet.meta2, count: triplet.meta })
+            .collect()
+    }
+
+    /// The contents of the `bulkfile_lookup_to_fileidx` section.
+    ///
+    /// Each value is an index into the `file_pairs` section, which holds the size and offset of each bulkfile.
+    /// The `meta` of a `bulkfiles_by_name` entry appears to be an index into this table,
+    /// so a bulkfile name is followed through here to the `FilePair` locating its data.
+    pub fn bulkfile_lookup_to_fileidx(&self) -> &[u32] {
+        self.bulkfile_file_indices.get(&self.buffer)
+    }
+
+    /// The contents of the `file_pairs` section, which has an entry for each music file, in the order they are stored.
+    ///
+    /// The music files are stored at the start of the data.arc, at `music_file_section_offset` in the header.
+    /// The offsets appear to be absolute offsets into the data.arc, so should land within that section,
+    /// but this has not been verified against a data.arc.
+    /// How `music_section_offset` relates to the music files is not known.
+    pub fn music_files(&self) -> &[FilePair] {
+        self.music_files.get(&self.buffer)
+    }
+
+    /// Read the data of the music file at `index` in `music_files`, exactly as it is stored.
+    ///
+    /// The offset of the `FilePair` is taken to be relative to the start of the data.arc, see `music_files`.
+    /// What format the data is in, and if it is compressed, is not known.
+    pub fn get_music_file(&mut self, index: usize) -> Result<Vec<u8>, GetFileError> {
+        let music_file = *self.music_files().get(index).ok_or(GetFileError::FileNotFound)?;
+        let offset = self.base_offset.checked_add(music_file.offset)
+            .ok_or(GetFileError::SectionOverrun { offset: music_file.offset, size: music_file.size, file_len: self.file_len })?;
+        self.read_range(offset, music_file.size)
+    }
+
+    /// The contents of the `another_hash_table` section, in the order they are stored.
+    ///
+    /// What the entries mean is not known.
+    /// The section is sized by the u8 `another_hash_table_size`, which may be wrong for some versions of the data.arc.
+    /// When it is wrong every section after it is misaligned, `DataArc::new_lenient` warns about this
+    /// when the first `folder_to_big_hash` entry no longer points at a `big_hashes` entry with the same hash.
+    pub fn another_hash_table(&self) -> &[EntryTriplet] {
+        self.another_hash_entries.get(&self.buffer)
+    }
+
+    /// The header at the start of the data.arc, with the offset of each section.
+    pub fn header(&self) -> &ArcHeader {
+        &self.header
+    }
+
+    /// The header at the start of the node section, with the number of entries in each section.
+    pub fn node_header(&self) -> &NodeHeader {
+        &self.node_header
+    }
+
+    /// Where the node section is stored in the data.arc, e.g. to copy it out for analysis.
+    pub fn node_section_range(&self) -> NodeSection {
+        self.node_section
+    }
+
+    /// The name, absolute offset and length in bytes of each section in the `ArcHeader`, in the order of the header.
+    ///
+    /// The length of a section is measured to the start of the next section after it, or to the end of the data.arc for the last section,
+    /// so any padding between sections is included. Sections starting at the same offset are given the same range.
+    /// An offset that overflows a `u64` in a corrupt header saturates to `u64::MAX`.
+    pub fn top_level_sections(&self) -> [(&'static str, u64, u64); 5] {
+        let mut sections = [
+            ("music_file_section", self.header.music_file_section_offset, 0),
+            ("file_section", self.header.file_section_offset, 0),
+            ("music_section", self.header.music_section_offset, 0),
+            ("node_section", self.header.node_section_offset, 0),
+            ("unk_section", self.header.unk_section_offset, 0),
+        ];
+        let mut starts: Vec<u64> = sections.iter().map(|&(_, offset, _)| self.base_offset.saturating_add(offset)).collect();
+        starts.sort_unstable();
+        for section in &mut sections {
+            let start = self.base_offset.saturating_add(section.1);
+            let end = starts.iter().copied().find(|&x| x > start).unwrap_or(self.file_len).max(start);
+            *section = (section.0, start, end - start);
+        }
+        sections
+    }
+
+    /// Where file data is read from.
+    pub fn backend_kind(&self) -> BackendKind {
+        match &self.backend {
+            Some(Backend::File(_)) => BackendKind::File,
+            #[cfg(feature = "mmap")]
+            Some(Backend::Mmap(_)) => BackendKind::Mmap,
+            Some(Backend::Memory(_)) => BackendKind::Memory,
+            Some(Backend::Reader(_)) => BackendKind::Reader,
+            None => BackendKind::MetadataOnly,
+        }
+    }
+
+    /// If files can be read from many threads at once, i.e. if `extract_many_parallel` can be used.
+    pub fn supports_concurrent_reads(&self) -> bool {
+        match self.backend_kind() {
+            BackendKind::Mmap | BackendKind::Memory => true,
+            BackendKind::File | BackendKind::Reader | BackendKind::MetadataOnly => false,
+        }
+    }
+
+    /// Whether the node section is stored compressed, with the sizes from its header.
+    pub fn node_format(&self) -> NodeFormat {
+        self.node_section.format
+    }
+
+    /// The entries of the `numbers` section, the last section of the node section, in the order they are stored.
+    ///
+    /// What the entries mean is not known. The size of the section is also not known, up to `file_lookup_count` entries are read,
+    /// stopping early at the end of the node section.
+    /// They are not the values of the `file_lookup` hash table, as each `file_lookup` entry already pairs a hash with the index of its tree entry.
+    pub fn file_lookup_numbers(&self) -> Vec<EntryPair> {
+        self.numbers_bytes().chunks_exact(ENTRY_PAIR_SIZE).map(read_pair).collect()
+    }
+
+    /// The bytes of the `file_lookup_count` entries of the `numbers` section read by `file_lookup_numbers`.
+    fn numbers_bytes(&self) -> &[u8] {
+        let end = known_node_end(self.numbers, self.node_header.file_lookup_count, self.buffer.len());
+        self.buffer.get(self.numbers..end).unwrap_or(&[])
+    }
+
+    /// The bytes of the decompressed node section after the `file_lookup_count` entries of the `numbers` section read by `file_lookup_numbers`.
+    ///
+    /// These are usually padding, bytes that aren't zero may be a section that isn't known yet, `DataArc::new_lenient` warns about them.
+    pub fn trailing_node_bytes(&self) -> &[u8] {
+        let end = known_node_end(self.numbers, self.node_header.file_lookup_count, self.buffer.len());
+        self.buffer.get(end..).unwrap_or(&[])
+    }
+
+    /// Decode `count` entries starting at `offset` in the decompressed node section, after the node header,
+    /// instead of at the offset found from the counts in the node header.
+    ///
+    /// This is for investigating a data.arc whose sections are misaligned, see `DataArc::check_alignment`.
+    /// The offsets from `section_ranges` are relative to the same position.
+    pub fn decode_section_at<T: SectionEntry>(&self, offset: usize, count: usize) -> Result<Vec<T>, ParseError> {
+        let end = count.checked_mul(T::SIZE).and_then(|size| size.checked_add(offset))
+            .ok_or_else(|| format_err!("The {} entries at {:#x} overflow the size of the node section", count, offset))?;
+        let range = section_range(&self.buffer, "requested", offset, end)?;
+        Ok(self.buffer[range].chunks_exact(T::SIZE).map(T::decode).collect())
+    }
+
+    /// The range of every section within the decompressed node section, after the node header, keyed by the name of the section.
+    ///
+    /// Each section ends where the next one starts, except `numbers`, whose size is not known, so it is assumed to run to the end of the node section.
+    /// A section may run past the end of the node section when the data.arc is corrupt, `DataArc::self_check` reports this.
+    pub fn section_ranges(&self) -> BTreeMap<&'static str, Range<usize>> {
+        let starts = self.section_starts();
+        let ends = starts.iter().skip(1).map(|&(_, start)| start).chain(Some(self.buffer.len().max(self.numbers)));
+        starts.iter().zip(ends).map(|(&(name, start), end)| (name, start..end)).collect()
+    }
+
+    /// The undecoded bytes of the section named `name`, one of the names from `section_ranges`, e.g. `trees`.
+    ///
+    /// Returns `None` when there is no section named `name` or the section runs past the end of the node section.
+    pub fn section_bytes(&self, name: &str) -> Option<&[u8]> {
+        let range = self.section_ranges().remove(name)?;
+        self.buffer.get(range)
+    }
+
+    /// The start of every section within the decompressed node section, in the order they are stored.
+    fn section_starts(&self) -> [(&'static str, usize); 16] {
+        [
+            ("bulkfile_category_info", 0),
+            ("bulkfile_hash_lookup", self.bulkfile_hash_lookup),
+            ("bulkfiles_by_name", self.bulkfiles_by_name),
+            ("bulkfile_lookup_to_fileidx", self.bulkfile_lookup_to_fileidx),
+            ("file_pairs", self.file_pairs),
+            ("another_hash_table", self.another_hash_table),
+            ("big_hashes", self.big_hashes),
+            ("big_files", self.big_files),
+            ("folder_hash_lookup", self.folder_hash_lookup),
+            ("trees", self.trees),
+            ("sub_files1", self.sub_files1),
+            ("sub_files2", self.sub_files2),
+            ("folder_to_big_hash", self.folder_to_big_hash),
+            ("file_lookup_buckets", self.file_lookup_buckets),
+            ("file_lookup", self.file_lookup),
+            ("numbers", self.numbers),
+        ]
+    }
+
+    /// The number of entries in every section of the node section, summed from the counts in the node header.
+    ///
+    /// This includes `another_hash_table_size` but not `file_information_count`, as no section is known to be sized by it.
+    pub fn total_entry_count(&self) -> usize {
+        let counts: u64 = self.node_header.counts().iter().map(|&(_, count)| count as u64).sum();
+        (counts + self.node_header.another_hash_table_size as u64) as usize
+    }
+
+    /// The `file_information_count` field of the node header.
+    ///
+    /// No section is known to be sized by it, it is exposed so this can be investigated.
+    pub fn file_information_count(&self) -> u32 {
+        self.node_header.file_information_count
+    }
+
+    /// Set the labels used to give names to the hashes in the data.arc.
+    pub fn set_labels(&mut self, labels: HashLabels) {
+        self.labels = Some(labels);
+    }
+
+    /// The labels set by `set_labels`.
+    pub fn labels(&self) -> Option<&HashLabels> {
+        self.labels.as_ref()
+    }
+
+    /// Check how many of the file paths have a label.
+    /// When no labels have been set every path is unnamed.
+    pub fn name_coverage(&self) -> NameCoverage {
+        let mut seen = HashSet::new();
+        let mut named = 0;
+        let mut unnamed = vec!();
+        for tree in self.tree_entries() {
+            let hash = tree.path.hash;
+            if seen.insert(hash) {
+                if self.labels.as_ref().and_then(|x| x.resolve(hash)).is_some() {
+                    named += 1;
+                } else {
+                    unnamed.push(hash);
+                }
+            }
+        }
+        NameCoverage { total_hashes: seen.len(), named, unnamed }
+    }
+
+    /// Every file path hash without a label, with the labels of its extension and folder, in the order they are stored.
+    ///
+    /// The extension and folder of a file are often labelled when its path is not, which helps to guess the path.
+    /// Each path hash is only listed once, the same as `NameCoverage::unnamed`.
+    /// When no labels have been set every path is unnamed.
+    pub fn unnamed_entries(&self) -> Vec<UnnamedEntry> {
+        let resolve = |hash| self.labels.as_ref().and_then(|x| x.resolve(hash)).map(|x| x.to_string());
+        let mut seen = HashSet::new();
+        self.tree_entries().iter()
+            .filter(|tree| seen.insert(tree.path.hash))
+            .filter(|tree| resolve(tree.path.hash).is_none())
+            .map(|tree| UnnamedEntry {
+                path_hash: tree.path.hash,
+                ext: resolve(tree.ext.hash),
+                folder: resolve(tree.folder.hash),
+            })
+            .collect()
+    }
+
+    /// The path of the file described by `entry`.
+    ///
+    /// When the labels have no label for the full path it is rebuilt from the labels of its folder and file name,
+    /// so a path can be recovered when only its components have been labelled.
+    /// Returns `None` when neither the path nor both of its components have a label.
+    pub fn reconstruct_path(&self, entry: &TreeEntry) -> Option<String> {
+        let labels = self.labels.as_ref()?;
+        if let Some(path) = labels.resolve(entry.path.hash) {
+            return Some(path.to_string());
+        }
+
+        let folder = labels.resolve(entry.folder.hash)?;
+        let file = labels.resolve(entry.file.hash)?;
+        if folder.ends_with('/') {
+            Some(format!("{}{}", folder, file))
+        } else {
+            Some(format!("{}/{}", folder, file))
+        }
+    }
+
+    /// The path of every tree entry, in the order they are stored in the `trees` section.
+    ///
+    /// Each path is found with `reconstruct_path`, falling back to the path hash formatted as `0x{hash:x}`,
+    /// so every path is a hash when no labels have been set.
+    pub fn file_paths(&self) -> Vec<String> {
+        self.tree_entries().iter()
+            .map(|tree| self.reconstruct_path(tree).unwrap_or_else(|| format!("0x{:x}", tree.path.hash)))
+            .collect()
+    }
+
+    /// The extension of the file at `file_name`, found from the ext hash of its tree entry.
+    /// An extension missing from the labels is named by its hash formatted as `0x{hash:x}`.
+    ///
+    /// Returns `None` when the file does not exist.
+    pub fn extension_of(&self, file_name: &str) -> Option<String> {
+        let tree = self.lookup_by_hash(hash40(file_name))?;
+        Some(match &self.labels {
+            Some(labels) => labels.resolve_or_hex(tree.ext.hash),
+            None => format!("0x{:x}", tree.ext.hash),
+        })
+    }
+
+    /// Count the files of each extension, by the hash40 of the extension.
+    /// Extensions are in the order they are first found in the `trees` section.
+    pub fn extension_histogram(&self) -> Vec<(u64, usize)> {
+        count_in_order(self.tree_entries().iter().map(|tree| tree.ext.hash))
+    }
+
+    /// Count the files of each extension, by the extension name.
+    /// Extensions missing from the labels are named by their hash formatted as `0x{hash:x}`.
+    /// Extensions are in the order they are first found in the `trees` section.
+    ///
+    /// Returns `None` when no labels have been set.
+    pub fn extension_histogram_named(&self) -> Option<Vec<(String, usize)>> {
+        let labels = self.labels.as_ref()?;
+        Some(count_in_order(self.tree_entries().iter().map(|tree| labels.resolve_or_hex(tree.ext.hash))))
+    }
+
+    /// The hash40 of every folder path, in the order they are first found in the `big_hashes` and `folder_to_big_hash` sections.
+    /// Folders found in both sections are only included once.
+    pub fn folder_hashes(&self) -> Vec<u64> {
+        let mut seen = HashSet::new();
+        let mut folders = vec!();
+        let big_hashes = self.big_hashes().map(|x| x.path.hash);
+        let folder_to_big_hash = (0..self.node_header.folder_count as usize)
+            .map_while(|i| self.buffer.get(self.folder_to_big_hash + ENTRY_PAIR_SIZE * i..).filter(|x| x.len() >= ENTRY_PAIR_SIZE))
+            .map(|x| read_pair(x).hash);
+        for hash in big_hashes.chain(folder_to_big_hash) {
+            if seen.insert(hash) {
+                folders.push(hash);
+            }
+        }
+        folders
+    }
+
+    /// Every tree entry whose folder hash is not one of `folder_hashes`, in the order they are stored in the `trees` section.
+    ///
+    /// These are either dangling references in the data.arc or a sign that a section was parsed wrong.
+    pub fn orphan_entries(&self) -> Vec<&TreeEntry> {
+        let folders: HashSet<u64> = self.folder_hashes().into_iter().collect();
+        self.tree_entries().iter().filter(|tree| !folders.contains(&tree.folder.hash)).collect()
+    }
+
+    /// The same as `folder_hashes` but with the hashes resolved to folder paths.
+    /// Folders missing from the labels are given their hash formatted as `0x{hash:x}`.
+    ///
+    /// Returns `None` when no labels have been set.
+    pub fn folder_paths(&self) -> Option<Vec<String>> {
+        let labels = self.labels.as_ref()?;
+        Some(self.folder_hashes().into_iter().map(|x| labels.resolve_or_hex(x)).collect())
+    }
+
+    /// Every entry of the `big_hashes` section, which has an entry for each folder, in the order they are stored.
+    pub fn big_hashes(&self) -> impl Iterator<Item = BigHashEntry> + '_ {
+        (0..self.node_header.folder_count as usize).map_while(move |i| self.big_hash_entry(i))
+    }
+
+    /// The entry at `index` in the `big_hashes` section, `None` when it is past the end of the node section.
+    fn big_hash_entry(&self, index: usize) -> Option<BigHashEntry> {
+        let start = self.big_hashes.checked_add(BIG_HASH_ENTRY_SIZE.checked_mul(index)?)?;
+        self.buffer.get(start..start + BIG_HASH_ENTRY_SIZE).map(read_big_hash_entry)
+    }
+
+    /// TODO: Binary search
+    fn bucket_search(&self, hash: u64, bucket: &HashBucket) -> Result<EntryPair, GetFileError> {
+        let start_index = self.file_lookup + ENTRY_PAIR_SIZE * bucket.index as usize;
+        for i in 0..bucket.num_entries as usize {
+            let pair = match self.buffer.get(start_index + ENTRY_PAIR_SIZE * i ..) {
+                Some(entry) if entry.len() >= ENTRY_PAIR_SIZE => read_pair(entry),
+                _ => break,
+            };
+            if pair.hash == hash {
+                return Ok(pair);
+            }
+        }
+        Err(GetFileError::FileNotFound)
+    }
+
+    /// Print the first entry of every section to stdout.
+    ///
+    /// The same is logged at the trace level of the `log` crate when a data.arc is parsed.
+    pub fn debug_print(&self) -> Result<(), Error> {
+        // TODO: print all elements
+        for (name, entry) in self.first_entries() {
+            println!("{}: {}", name, entry);
+        }
+        Ok(())
+    }
+
+    /// The name of every section with its first entry formatted for debugging, or `empty`.
+    fn first_entries(&self) -> Vec<(&'static str, String)> {
+        vec!(
+            ("bulkfile_category_info", self.format_first("bulkfile_category_info", ENTRY_TRIPLET_SIZE, read_triplet)),
+            ("bulkfile_hash_lookup", self.format_first("bulkfile_hash_lookup", ENTRY_PAIR_SIZE, read_pair)),
+            ("bulkfiles_by_name", self.format_first("bulkfiles_by_name", ENTRY_TRIPLET_SIZE, read_triplet)),
+            ("bulkfile_lookup_to_fileidx", self.format_first("bulkfile_lookup_to_fileidx", 4, LittleEndian::read_u32)),
+            ("file_pairs", self.format_first("file_pairs", FILE_PAIR_SIZE, read_file_pair)),
+            ("another_hash_table", self.format_first("another_hash_table", ENTRY_TRIPLET_SIZE, read_triplet)),
+            ("big_hashes", self.format_first("big_hashes", BIG_HASH_ENTRY_SIZE, read_big_hash_entry)),
+            ("big_files", self.format_first("big_files", BIG_FILE_ENTRY_SIZE, BigFileEntry::decode)),
+            ("folder_hash_lookup", self.format_first("folder_hash_lookup", ENTRY_PAIR_SIZE, read_pair)),
+            ("trees", self.format_first("trees", TREE_ENTRY_SIZE, read_tree_entry)),
+            ("sub_files1", self.format_first("sub_files1", FILE_ENTRY_SIZE, read_file_entry)),
+            ("sub_files2", self.format_first("sub_files2", FILE_ENTRY_SIZE, read_file_entry)),
+            ("folder_to_big_hash", self.format_first("folder_to_big_hash", ENTRY_PAIR_SIZE, read_pair)),
+            ("file_lookup_buckets", self.format_first("file_lookup_buckets", HASH_BUCKET_SIZE, |data| data.pread_with::<HashBucket>(0, LE).unwrap_or_default())),
+            ("file_lookup", self.format_first("file_lookup", ENTRY_PAIR_SIZE, read_pair)),
+            // section_bytes runs numbers to the end of the node section, so only the entries read by file_lookup_numbers are used
+            ("numbers", format_entry(Some(self.numbers_bytes()), ENTRY_PAIR_SIZE, read_pair)),
+        )
+    }
+
+    /// Format the first entry of the section `name`, decoded by `decode`, or `empty` when the section is empty.
+    fn format_first<T: fmt::Debug>(&self, name: &str, entry_size: usize, decode: impl Fn(&[u8]) -> T) -> String {
+        format_entry(self.section_bytes(name), entry_size, decode)
+    }
+}
+
+/// Format the first entry of `data`, decoded by `decode`, or `empty` when it is shorter than one entry.
+fn format_entry<T: fmt::Debug>(data: Option<&[u8]>, entry_size: usize, decode: impl Fn(&[u8]) -> T) -> String {
+    match data.filter(|data| data.len() >= entry_size) {
+        Some(data) => format!("{:x?}", decode(data)),
+        None => "empty".to_string(),
+    }
+}
+
+/// Count the occurrences of each value, in the order each value first occurs.
+fn count_in_order<T: Hash + Eq + Clone>(values: impl Iterator<Item = T>) -> Vec<(T, usize)> {
+    let mut indexes = HashMap::new();
+    let mut counts: Vec<(T, usize)> = vec!();
+    for value in values {
+        let index = *indexes.entry(value.clone()).or_insert_with(|| {
+            counts.push((value, 0));
+            counts.len() - 1
+        });
+        counts[index].1 += 1;
+    }
+    counts
+}
+
+/// Check the size of the node section once decompressed is at most `max_size`, before it is allocated.
+fn check_node_limit(size: u64, max_size: u64) -> Result<(), ParseError> {
+    if size > max_size {
+        return Err(ParseError::NodeTooLarge { size, max_size });
+    }
+    Ok(())
+}
+
+/// Fail when the `count_name` count of the node header is larger than `max_entries`.
+fn check_entry_count(count_name: &'static str, count: u32, max_entries: u64) -> Result<(), ParseError> {
+    if count as u64 > max_entries {
+        return Err(ParseError::SectionOverrun { count_name, count, max_entries });
+    }
+    Ok(())
+}
+
+/// Read the magic number, which must be `magic`, and the header that follows it.
+fn read_arc_header<R: Read>(reader: &mut R, magic: u64) -> Result<ArcHeader, ParseError> {
+    let mut buffer = vec!(0; 8 + ARC_HEADER_SIZE);
+    let mut len = 0;
+    while len < buffer.len() {
+        match reader.read(&mut buffer[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    if len < 8 {
+        return Err(ParseError::Truncated { len: len as u64 });
+    }
+    if LittleEndian::read_u64(&buffer) != magic {
+        return Err(ParseError::NotDataArc);
+    }
+    if len < buffer.len() {
+        return Err(ParseError::Truncated { len: len as u64 });
+    }
+    Ok(buffer.pread_with(8, LE)?)
+}
+
+/// Get the range of a section in the node section, failing if it runs past the end of the node section.
+fn section_range(buffer: &[u8], name: &str, start: usize, end: usize) -> Result<Range<usize>, Error> {
+    if end <= buffer.len() {
+        Ok(start..end)
+    } else {
+        Err(format_err!("The {} section at {:#x}..{:#x} runs past the end of the node section of size {:#x}", name, start, end, buffer.len()))
+    }
+}
+
+
+/// Check that each of `sections`, the name of a section and where it ends, fits within the node section of `len` bytes.
+///
+/// When `warnings` is `Some`, only the first section that doesn't fit is pushed to it, as every section after it doesn't fit either.
+/// Returns whether every section fits, so the caller can skip checks that would report the same truncation again.
+fn check_sections_fit(warnings: &mut Option<&mut Vec<ParseWarning>>, len: usize, sections: &[(&'static str, usize)]) -> Result<bool, ParseError> {
+    if let Some(&(section, needed)) = sections.iter().find(|&&(_, end)| end > len) {
+        let error = ParseError::TruncatedSection { section, needed, available: len };
+        match warnings {
+            Some(warnings) => warnings.push(ParseWarning { section, error }),
+            None => return Err(error),
+        }
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// The end of the `numbers` section at `numbers` within a node section of `len` bytes, assuming it has `file_lookup_count` entries.
+fn known_node_end(numbers: usize, file_lookup_count: u32, len: usize) -> usize {
+    numbers.saturating_add(ENTRY_PAIR_SIZE * file_lookup_count as usize).min(len)
+}
+
+/// Check that no other section of `header` starts within the node section, which is `stored_size` bytes as stored.
+fn check_node_overlap(header: &ArcHeader, stored_size: u64) -> Result<(), ParseError> {
+    let node_start = header.node_section_offset;
+    let node_end = node_start.saturating_add(stored_size);
+    let sections = [
+        ("music_file_section_offset", header.music_file_section_offset),
+        ("file_section_offset", header.file_section_offset),
+        ("music_section_offset", header.music_section_offset),
+        ("unk_section_offset", header.unk_section_offset),
+    ];
+    for &(section, offset) in &sections {
+        // A section starting at the same offset as the node section is taken to be empty
+        if offset > node_start && offset < node_end {
+            return Err(ParseError::SectionOverlap { section, offset, node_end });
+        }
+    }
+    Ok(())
+}
+
+/// Check that the first `folder_to_big_hash` entry points at a `big_hashes` entry with the same hash,
+/// returning the reason when it doesn't.
+fn check_big_hash_alignment(buffer: &[u8], big_hashes: usize, folder_to_big_hash: usize, folder_count: u32) -> Option<Error> {
+    if folder_count == 0 {
+        return None;
+    }
+    let pair = read_pair(buffer.get(folder_to_big_hash..folder_to_big_hash + ENTRY_PAIR_SIZE)?);
+    let start = big_hashes + BIG_HASH_ENTRY_SIZE * pair.meta as usize;
+    match buffer.get(start..start + BIG_HASH_ENTRY_SIZE).map(read_big_hash_entry) {
+        Some(big_hash) if big_hash.path.hash == pair.hash => None,
+        _ => Some(format_err!(
+            "The first folder_to_big_hash entry {:#x} does not match big_hashes entry {}, \
+            the sections after another_hash_table may be misaligned",
+            pair.hash, pair.meta
+        )),
+    }
+}
+
+/// Where file data is read from.
+enum Backend {
+    File(File),
+    #[cfg(feature = "mmap")]
+    Mmap(memmap2::Mmap),
+    Memory(Vec<u8>),
+    Reader(Box<dyn ReadSeek>),
+}
+
+/// A reader that `DataArc::from_reader` can keep, an object safe combination of the traits it needs.
+trait ReadSeek: Read + Seek + Send + Sync {}
+
+impl<T: Read + Seek + Send + Sync> ReadSeek for T {}
+
+/// Where the data of a file is stored.
+struct FileLocation {
+    /// Absolute offset of the data in the data.arc
+    offset: u64,
+    /// Index of `sub_file` in the `sub_files1` section
+    index: usize,
+    sub_file: FileEntry,
+}
+
+impl FileLocation {
+    /// The absolute range of the stored data in the data.arc
+    fn range(&self) -> std::ops::Range<usize> {
+        self.offset as usize .. (self.offset + self.sub_file.comp_size as u64) as usize
+    }
+}
+
+fn check_compression(sub_file: &FileEntry) -> Result<(), GetFileError> {
+    if !sub_file.suboffset_decompressed() && !sub_file.suboffset_compressed_zstd() {
+        return Err(GetFileError::UnsupportedCompression { flags: sub_file.flags });
+    }
+
+    Ok(())
+}
+
+/// The data of `sub_file` from the `stored` data, which is returned as is when the file is stored uncompressed.
+fn decompress(decompressor: &dyn Decompressor, sub_file: &FileEntry, stored: Vec<u8>) -> Result<Vec<u8>, GetFileError> {
+    check_compression(sub_file)?;
+    if sub_file.suboffset_decompressed() {
+        return Ok(stored);
+    }
+
+    let decomp_size = sub_file.decomp_size as usize;
+    let buffer_decomp = decompressor.decompress(&stored, decomp_size)?;
+    if buffer_decomp.len() != decomp_size {
+        return Err(GetFileError::DecompressSizeMismatch { expected: decomp_size, actual: buffer_decomp.len() });
+    }
+
+    Ok(buffer_decomp)
+}
+
+fn hash40(name: &str) -> u64 {
+    hash40_debug(name).packed
+}
+
+/// The parts of a hash40, returned by `hash40_debug`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hash40Debug {
+    /// The CRC32 of the text
+    pub crc32: u32,
+    /// The length of the text truncated to a byte
+    pub length: u8,
+    /// `crc32` in the low 32 bits and `length` in the next 8 bits.
+    /// This is the 0x28 bit value stored in the first 5 bytes of an `EntryPair` or `EntryTriplet`.
+    pub packed: u64,
+}
+
+/// Hash `text` the same way the data.arc hashes paths, returning each part of the hash.
+pub fn hash40_debug(text: &str) -> Hash40Debug {
+    let crc32 = crc::crc32::checksum_ieee(text.as_bytes());
+    let length = text.len() as u8;
+    let packed = crc32 as u64 | (length as u64) << 32;
+    Hash40Debug { crc32, length, packed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A data.arc with a file in a folder, a file in a subfolder and an uncompressed file, built by `ArcBuilder`.
+    fn build_arc() -> Vec<u8> {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("fighter/mario/model.numdlb", b"mario model", true);
+        builder.add_file("fighter/mario/motion/body.nuanmb", &[7; 0x100], true);
+        builder.add_file("ui/param.prc", b"uncompressed", false);
+        builder.build().unwrap()
+    }
+
+    /// The absolute offset of the node section of `data`.
+    fn node_offset(data: &[u8]) -> usize {
+        LittleEndian::read_u64(&data[0x20..]) as usize
+    }
+
+    /// Overwrite the bytes at `offset` in the section `section` of the uncompressed node section of `data` with `bytes`.
+    fn patch_section(data: &mut [u8], section: &str, offset: usize, bytes: &[u8]) {
+        let start = DataArc::parse_bytes(data).unwrap().section_ranges()[section].start;
+        let at = node_offset(data) + NODE_HEADER_SIZE + start + offset;
+        data[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Replace the uncompressed node section of `data`, which must be at its end, with a compressed node section
+    /// whose zstd frame is `data_start` bytes after the start of the `CompressedNodeHeader`.
+    fn compress_node(data: &[u8], data_start: u32) -> Vec<u8> {
+        let offset = node_offset(data);
+        let node = &data[offset..];
+        let frame = zstd::block::compress(node, 0).unwrap();
+        let mut compressed = data[..offset].to_vec();
+        for &value in &[data_start, node.len() as u32, frame.len() as u32, frame.len() as u32] {
+            compressed.extend_from_slice(&value.to_le_bytes());
+        }
+        compressed.resize(offset + data_start as usize, 0);
+        compressed.extend_from_slice(&frame);
+        compressed
+    }
+
+    /// Everything that can be read from `data_arc` about its files, to compare data.arcs that should be the same.
+    fn contents(data_arc: &mut DataArc) -> Vec<String> {
+        let mut contents = vec!(
+            format!("{:?}", data_arc.tree_entries()),
+            format!("{:?}", data_arc.file_entries()),
+            format!("{:?}", data_arc.big_hashes().collect::<Vec<_>>()),
+            format!("{:?}", data_arc.big_files().collect::<Vec<_>>()),
+        );
+        for path in &["fighter/mario/model.numdlb", "fighter/mario/motion/body.nuanmb", "ui/param.prc"] {
+            contents.push(format!("{:?}", data_arc.get_file(path)));
+        }
+        contents
+    }
+
+    #[test]
+    fn compressed_node_matches_uncompressed() {
+        let uncompressed = build_arc();
+        let compressed = compress_node(&uncompressed, COMPRESSED_NODE_HEADER_SIZE as u32);
+        let mut uncompressed = DataArc::parse_owned(uncompressed).unwrap();
+        let mut compressed = DataArc::parse_owned(compressed).unwrap();
+        assert!(matches!(uncompressed.node_format(), NodeFormat::Uncompressed { .. }));
+        assert!(matches!(compressed.node_format(), NodeFormat::Compressed { .. }));
+        assert_eq!(contents(&mut compressed), contents(&mut uncompressed));
+    }
+
+    #[test]
+    fn file_past_end_is_section_overrun() {
+        let mut data = build_arc();
+        // The offset of the first FileEntry, in units of 4 bytes
+        patch_section(&mut data, "sub_files1", 0, &0x1000_0000u32.to_le_bytes());
+        let file_len = data.len() as u64;
+        let mut data_arc = DataArc::parse_owned(data).unwrap();
+        match data_arc.get_file("fighter/mario/model.numdlb") {
+            Err(GetFileError::SectionOverrun { size, file_len: len, .. }) => {
+                assert_eq!(len, file_len);
+                assert_ne!(size, 0);
+            }
+            other => panic!("expected SectionOverrun, got {:?}", other),
+        }
+        // The other files are still found
+        assert_eq!(data_arc.get_file("ui/param.prc").unwrap(), b"uncompressed");
+    }
+
+    #[test]
+    fn file_offset_overflow_is_section_overrun() {
+        let mut data = build_arc();
+        patch_section(&mut data, "big_files", 0, &u64::MAX.to_le_bytes());
+        let mut data_arc = DataArc::parse_owned(data).unwrap();
+        assert!(matches!(data_arc.get_file("ui/param.prc"), Err(GetFileError::SectionOverrun { offset: u64::MAX, .. })));
+        assert_eq!(data_arc.physical_files().count(), 0);
+    }
+
+    #[test]
+    fn absolute_offset_round_trip() {
+        let mut data_arc = DataArc::parse_owned(build_arc()).unwrap();
+        for &rel in &[0, 1, 0x1234, u64::MAX - data_arc.file_section_start()] {
+            let abs = data_arc.to_absolute_offset(rel).unwrap();
+            assert_eq!(data_arc.to_relative_offset(abs), Some(rel));
+        }
+        assert_eq!(data_arc.to_absolute_offset(u64::MAX), None);
+        assert_eq!(data_arc.to_relative_offset(data_arc.file_section_start() - 1), None);
+
+        data_arc.header.file_section_offset = u64::MAX;
+        assert_eq!(data_arc.to_absolute_offset(1), None);
+    }
+
+    #[test]
+    fn top_level_section_past_u64_max() {
+        let mut data_arc = DataArc::parse_owned(build_arc()).unwrap();
+        data_arc.base_offset = 0x10;
+        data_arc.header.unk_section_offset = u64::MAX - 1;
+        let sections = data_arc.top_level_sections();
+        assert_eq!(sections[4], ("unk_section", u64::MAX, 0));
+
+        let violations = data_arc.self_check().unwrap_err();
+        assert!(violations.iter().any(|violation| violation.starts_with("The unk_section at 0xffffffffffffffff starts past the end")));
+    }
+
+    #[test]
+    fn unknown_section_offset_overflow_is_section_overrun() {
+        let mut data_arc = DataArc::parse_owned(build_arc()).unwrap();
+        data_arc.base_offset = 0x10;
+        data_arc.header.unk_section_offset = u64::MAX - 1;
+        assert!(matches!(data_arc.unknown_section_bytes(4), Err(GetFileError::SectionOverrun { offset: u64::MAX, size: 4, .. })));
+    }
+
+    #[test]
+    fn file_entry_for_path() {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/first.bin", b"12345678", false);
+        builder.add_file("a/second.bin", b"hello", false);
+        let mut data = builder.build().unwrap();
+
+        let data_arc = DataArc::parse_bytes(&data).unwrap();
+        let tree = data_arc.lookup_by_hash(hash40("a/second.bin")).unwrap();
+        let file_entry = data_arc.file_entry_for(tree).unwrap();
+        assert_eq!((file_entry.offset, file_entry.comp_size, file_entry.decomp_size), (2, 5, 5));
+
+        let index = data_arc.tree_entries().iter().position(|x| x.path.hash == tree.path.hash).unwrap();
+        let flags = tree.flags | 0x200000;
+        patch_section(&mut data, "trees", index * TREE_ENTRY_SIZE + 0x24, &flags.to_le_bytes());
+        let data_arc = DataArc::parse_bytes(&data).unwrap();
+        let tree = data_arc.lookup_by_hash(hash40("a/second.bin")).unwrap();
+        assert!(tree.redirect());
+        assert!(data_arc.file_entry_for(tree).is_none());
+    }
+
+    #[test]
+    fn zero_file_size_is_invalid_node_size() {
+        let mut data = build_arc();
+        let offset = node_offset(&data);
+        data[offset..offset + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(DataArc::parse_owned(data).err(), Some(ParseError::InvalidNodeSize { size: 0, .. })));
+    }
+
+    #[test]
+    fn iteration_order_is_stable() {
+        let data = build_arc();
+        let first = DataArc::parse_owned(data.clone()).unwrap();
+        let second = DataArc::parse_owned(data).unwrap();
+        let order = |data_arc: &DataArc| (
+            format!("{:?}", data_arc.index_rows().collect::<Vec<_>>()),
+            format!("{:?}", data_arc.physical_files().collect::<Vec<_>>()),
+            data_arc.file_paths(),
+            data_arc.big_hashes().map(|x| x.path.hash).collect::<Vec<_>>(),
+        );
+        assert_eq!(order(&first), order(&second));
+
+        // The trees section is in the order the files were added to the ArcBuilder
+        let paths: Vec<u64> = first.tree_entries().iter().map(|tree| tree.path.hash).collect();
+        assert_eq!(paths, vec!(hash40("fighter/mario/model.numdlb"), hash40("fighter/mario/motion/body.nuanmb"), hash40("ui/param.prc")));
+    }
+
+    #[test]
+    fn compressed_node_data_start_past_header() {
+        let uncompressed = build_arc();
+        // Bytes between the CompressedNodeHeader and the zstd frame would corrupt the frame if read from the end of the header
+        let compressed = compress_node(&uncompressed, 0x40);
+        let mut uncompressed = DataArc::parse_owned(uncompressed).unwrap();
+        let mut compressed = DataArc::parse_owned(compressed).unwrap();
+        assert_eq!(compressed.node_section_range().size, 0x40 + match compressed.node_format() {
+            NodeFormat::Compressed { comp_size, .. } => comp_size as u64,
+            NodeFormat::Uncompressed { .. } => panic!("expected a compressed node section"),
+        });
+        assert_eq!(contents(&mut compressed), contents(&mut uncompressed));
+    }
+
+    #[test]
+    fn unknown_compression_is_unsupported() {
+        let mut data = build_arc();
+        // The flags of the first FileEntry
+        patch_section(&mut data, "sub_files1", 0xc, &0x0500_0000u32.to_le_bytes());
+        let mut data_arc = DataArc::parse_owned(data).unwrap();
+        assert_eq!(data_arc.file_entries()[0].compression(), Compression::Unknown(0x0500_0000));
+        assert!(matches!(data_arc.get_file("fighter/mario/model.numdlb"), Err(GetFileError::UnsupportedCompression { flags: 0x0500_0000 })));
+        assert_eq!(data_arc.get_file("fighter/mario/motion/body.nuanmb").unwrap(), vec!(7; 0x100));
+    }
+
+    #[test]
+    fn bucket_lookup_matches_linear_scan() {
+        let mut builder = ArcBuilder::new();
+        let paths: Vec<String> = (0..500).map(|i| format!("folder{}/file{}.bin", i % 7, i)).collect();
+        for path in &paths {
+            builder.add_file(path, path.as_bytes(), false);
+        }
+        let data_arc = DataArc::parse_owned(builder.build().unwrap()).unwrap();
+
+        let missing = (0..100).map(|i| format!("missing/file{}.bin", i));
+        for path in paths.iter().cloned().chain(missing) {
+            let hash = hash40(&path);
+            let linear = data_arc.tree_entries().iter().position(|tree| tree.path.hash == hash);
+            assert_eq!(data_arc.tree_index_of_hash(hash), linear, "{}", path);
+            let bucket = data_arc.lookup_by_hash(hash).map(|tree| tree as *const TreeEntry);
+            assert_eq!(bucket, linear.map(|i| &data_arc.tree_entries()[i] as *const TreeEntry), "{}", path);
+        }
+    }
+
+    /// Replace the `file_lookup_buckets` and `file_lookup` sections of `data`, which ArcBuilder writes with one bucket per file,
+    /// with `num_buckets` buckets, so a bucket can hold more entries than there are buckets.
+    fn rebucket(data: &[u8], num_buckets: u32) -> Vec<u8> {
+        let data_arc = DataArc::parse_bytes(data).unwrap();
+        let file_lookup = data_arc.section_bytes("file_lookup").unwrap();
+        let mut buckets = vec!(vec!(); num_buckets as usize);
+        for entry in file_lookup.chunks_exact(ENTRY_PAIR_SIZE).take(data_arc.node_header().file_lookup_count as usize) {
+            buckets[(read_pair(entry).hash % num_buckets as u64) as usize].push(entry);
+        }
+
+        let node = node_offset(data);
+        let mut rebucketed = data[..node + NODE_HEADER_SIZE + data_arc.section_ranges()["file_lookup_buckets"].start].to_vec();
+        rebucketed.extend_from_slice(&0u32.to_le_bytes());
+        rebucketed.extend_from_slice(&num_buckets.to_le_bytes());
+        let mut index = 0u32;
+        for bucket in &buckets {
+            rebucketed.extend_from_slice(&index.to_le_bytes());
+            rebucketed.extend_from_slice(&(bucket.len() as u32).to_le_bytes());
+            index += bucket.len() as u32;
+        }
+        for entry in buckets.iter().flatten() {
+            rebucketed.extend_from_slice(entry);
+        }
+        rebucketed.resize(rebucketed.len().max(node + 0x100), 0);
+        let size = (rebucketed.len() - node) as u32;
+        rebucketed[node..node + 4].copy_from_slice(&size.to_le_bytes());
+        rebucketed
+    }
+
+    #[test]
+    fn bucket_search_past_first_bucket() {
+        let mut builder = ArcBuilder::new();
+        let paths: Vec<String> = (0..9).map(|i| format!("folder/file{}.bin", i)).collect();
+        for path in &paths {
+            builder.add_file(path, path.as_bytes(), false);
+        }
+        let mut data_arc = DataArc::parse_owned(rebucket(&builder.build().unwrap(), 2)).unwrap();
+        assert_eq!(data_arc.first_hash_bucket.num_entries, 2);
+
+        // With 9 files one bucket holds more entries than there are buckets, and both buckets are searched
+        assert!(paths.iter().any(|path| hash40(path) % 2 == 1));
+        for path in &paths {
+            let hash = hash40(path);
+            assert_eq!(data_arc.lookup_by_hash(hash).map(|tree| tree.path.hash), Some(hash), "{}", path);
+            assert_eq!(data_arc.get_file(path).unwrap(), path.as_bytes());
+        }
+    }
+
+    #[test]
+    fn shared_file_entries() {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/original.bin", b"shared", false);
+        builder.add_file("a/copy.bin", b"unused", false);
+        builder.add_file("a/other.bin", b"other", false);
+        let mut data = builder.build().unwrap();
+        // Point the FileEntry of the copy at the data of the original
+        let original = DataArc::parse_bytes(&data).unwrap().file_entries()[0];
+        patch_section(&mut data, "sub_files1", FILE_ENTRY_SIZE, &original.offset.to_le_bytes());
+
+        let data_arc = DataArc::parse_owned(data).unwrap();
+        let entry = |path| data_arc.file_entry_for(data_arc.lookup_by_hash(hash40(path)).unwrap()).unwrap();
+        let (original, copy, other) = (entry("a/original.bin"), entry("a/copy.bin"), entry("a/other.bin"));
+        assert!(!std::ptr::eq(original, copy));
+        assert!(data_arc.is_shared(original));
+        assert!(data_arc.is_shared(copy));
+        assert!(!data_arc.is_shared(other));
+        assert!(std::ptr::eq(data_arc.resolve_shared(copy), original));
+        assert!(std::ptr::eq(data_arc.resolve_shared(original), original));
+        assert!(std::ptr::eq(data_arc.resolve_shared(other), other));
+
+        let mut data_arc = data_arc;
+        assert_eq!(data_arc.get_file("a/copy.bin").unwrap(), b"shared");
+    }
+
+    #[test]
+    fn trailing_node_data_is_size_mismatch() {
+        let mut data = build_arc();
+        let node = node_offset(&data);
+        // The numbers section is assumed to hold file_lookup_count entries, so the extra bytes go after them
+        let data_arc = DataArc::parse_bytes(&data).unwrap();
+        let expected = data_arc.section_ranges()["numbers"].start + ENTRY_PAIR_SIZE * data_arc.node_header().file_lookup_count as usize;
+        data.resize(data.len().max(node + NODE_HEADER_SIZE + expected), 0);
+        data.extend_from_slice(&[1; 0x10]);
+        let size = (data.len() - node) as u32;
+        data[node..node + 4].copy_from_slice(&size.to_le_bytes());
+        let actual = data.len() - node - NODE_HEADER_SIZE;
+        match DataArc::parse_owned(data.clone()).err() {
+            Some(ParseError::SizeMismatch { expected: e, actual: a }) => assert_eq!((e, a), (expected, actual)),
+            other => panic!("expected SizeMismatch, got {:?}", other),
+        }
+
+        let options = ParseOptions::new().check_node_size(false).check_version(false);
+        let data_arc = DataArc::parse_owned_with_options(data, options).unwrap();
+        assert_eq!(data_arc.trailing_node_bytes(), &[1; 0x10]);
+    }
+
+    #[test]
+    fn zero_counts() {
+        let data_arc = DataArc::parse_owned(ArcBuilder::new().build().unwrap()).unwrap();
+        assert_eq!(data_arc.node_header().movie_count, 0);
+        assert_eq!(data_arc.node_header().music_file_count, 0);
+        assert_eq!(data_arc.node_header().tree_count, 0);
+
+        assert!(data_arc.movie_categories().is_empty());
+        assert!(data_arc.music_files().is_empty());
+        assert!(data_arc.bulkfile_lookup_to_fileidx().is_empty());
+        assert!(data_arc.another_hash_table().is_empty());
+        assert!(data_arc.tree_entries().is_empty());
+        assert!(data_arc.file_entries().is_empty());
+        assert_eq!(data_arc.file_lookup().count(), 0);
+        assert!(data_arc.file_paths().is_empty());
+        assert!(data_arc.folder_stats().is_empty());
+        assert!(data_arc.shared_groups().is_empty());
+        assert!(data_arc.physical_files().next().is_none());
+        assert!(data_arc.data_span().is_none());
+        assert_eq!(data_arc.compression_stats().ratio, 1.0);
+        assert_eq!(data_arc.first_entries().last().unwrap(), &("numbers", "empty".to_string()));
+        data_arc.self_check().unwrap();
+    }
+
+    /// A reader that fails every read with `kind`.
+    struct FailingReader(ErrorKind);
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> Result<usize, IOError> {
+            Err(IOError::from(self.0))
+        }
+    }
+
+    #[test]
+    fn read_arc_header_errors() {
+        let error = read_arc_header(&mut FailingReader(ErrorKind::PermissionDenied), DATA_ARC_MAGIC).err().unwrap();
+        assert_eq!(IOError::from(error).kind(), ErrorKind::PermissionDenied);
+
+        let error = read_arc_header(&mut Cursor::new(&[0; 0x100][..]), DATA_ARC_MAGIC).err().unwrap();
+        assert!(matches!(error, ParseError::NotDataArc));
+
+        let error = read_arc_header(&mut Cursor::new(&DATA_ARC_MAGIC.to_le_bytes()[..]), DATA_ARC_MAGIC).err().unwrap();
+        assert!(matches!(error, ParseError::Truncated { len: 8 }));
+    }
+
+    #[test]
+    fn truncated_section_is_one_warning() {
+        let mut data = build_arc();
+        let at = node_offset(&data) + 0xc;
+        data[at..at + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+        let mut warnings = vec!();
+        let data_arc = DataArc::parse_reader(&mut Cursor::new(&data), &ParseOptions::new(), Some(&mut warnings)).unwrap();
+        let sections: Vec<_> = warnings.iter().map(|warning| warning.section).collect();
+        assert_eq!(sections, ["trees"]);
+        assert!(matches!(warnings[0].error, ParseError::TruncatedSection { section: "trees", .. }));
+        assert!(data_arc.tree_entries().is_empty());
+        assert!(data_arc.file_entries().is_empty());
+    }
+
+    #[test]
+    fn truncated_file_is_decompress_failed() {
+        let mut data = build_arc();
+        let data_arc = DataArc::parse_owned(data.clone()).unwrap();
+        let comp_size = data_arc.file_entries()[1].comp_size;
+        // The comp_size of the second FileEntry, so only part of its zstd data is read
+        patch_section(&mut data, "sub_files1", FILE_ENTRY_SIZE + 4, &(comp_size - 1).to_le_bytes());
+        let mut data_arc = DataArc::parse_owned(data).unwrap();
+        assert!(matches!(data_arc.get_file("fighter/mario/motion/body.nuanmb"), Err(GetFileError::DecompressFailed { decomp_size: 0x100, .. })));
+    }
+
+    #[test]
+    fn truncated_node_is_decompress_failed() {
+        let uncompressed = build_arc();
+        let decomp_size = uncompressed.len() - node_offset(&uncompressed);
+        let mut data = compress_node(&uncompressed, COMPRESSED_NODE_HEADER_SIZE as u32);
+        let offset = node_offset(&data);
+        let comp_size = LittleEndian::read_u32(&data[offset + 0xc..]);
+        data[offset + 0xc..offset + 0x10].copy_from_slice(&(comp_size - 1).to_le_bytes());
+        match DataArc::parse_owned(data).err() {
+            Some(ParseError::DecompressFailed { decomp_size: size, .. }) => assert_eq!(size, decomp_size),
+            Some(err) => panic!("expected DecompressFailed, got {:?}", err),
+            None => panic!("expected DecompressFailed"),
+        }
+    }
+
+    #[test]
+    fn huge_decomp_size_is_node_too_large() {
+        let uncompressed = build_arc();
+        let mut data = compress_node(&uncompressed, COMPRESSED_NODE_HEADER_SIZE as u32);
+        let offset = node_offset(&data);
+        data[offset + 4..offset + 8].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+        assert!(matches!(DataArc::parse_owned(data).err(), Some(ParseError::NodeTooLarge { size: 0xffff_fff0, .. })));
+
+        // The limit applies to an uncompressed node section too
+        let options = ParseOptions::new().max_node_size(0x10);
+        assert!(matches!(DataArc::parse_owned_with_options(uncompressed, options).err(), Some(ParseError::NodeTooLarge { max_size: 0x10, .. })));
+    }
 }
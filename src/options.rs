@@ -0,0 +1,140 @@
+use crate::{DATA_ARC_MAGIC, Decompressor};
+
+/// Options for parsing a data.arc, passed to `DataArc::new_with_options`.
+#[derive(Debug)]
+pub struct ParseOptions {
+    pub(crate) magic_offset: u64,
+    pub(crate) expected_magic: u64,
+    pub(crate) max_entries_per_section: u64,
+    pub(crate) max_node_size: u64,
+    pub(crate) decompressor: Option<Box<dyn Decompressor>>,
+    pub(crate) lazy: bool,
+    pub(crate) validate_indices: bool,
+    pub(crate) check_alignment: bool,
+    pub(crate) check_node_size: bool,
+    pub(crate) check_version: bool,
+    #[cfg(feature = "mmap")]
+    pub(crate) mmap: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            magic_offset: 0,
+            expected_magic: DATA_ARC_MAGIC,
+            max_entries_per_section: 10_000_000,
+            max_node_size: 0x2000_0000,
+            decompressor: None,
+            lazy: false,
+            validate_indices: false,
+            check_alignment: false,
+            check_node_size: true,
+            check_version: true,
+            #[cfg(feature = "mmap")]
+            mmap: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// The offset in the file that the data.arc starts at, defaults to 0.
+    ///
+    /// Use this to parse a data.arc embedded in another file.
+    /// All offsets stored in the data.arc are treated as relative to this offset.
+    pub fn magic_offset(mut self, offset: u64) -> ParseOptions {
+        self.magic_offset = offset;
+        self
+    }
+
+    /// The magic number the data.arc must start with, defaults to `DATA_ARC_MAGIC`.
+    ///
+    /// Use this to parse a data.arc built with a different magic number, parsing fails with `ParseError::NotDataArc` when it doesn't match.
+    pub fn expected_magic(mut self, magic: u64) -> ParseOptions {
+        self.expected_magic = magic;
+        self
+    }
+
+    /// The largest number of entries any section of the node section may have, defaults to 10 million.
+    ///
+    /// Parsing fails with `ParseError::SectionOverrun` before anything is allocated for a larger section.
+    /// This guards against a corrupt data.arc causing a huge allocation.
+    pub fn max_entries_per_section(mut self, max_entries: u64) -> ParseOptions {
+        self.max_entries_per_section = max_entries;
+        self
+    }
+
+    /// The largest size of the node section once decompressed, defaults to 512 MiB.
+    ///
+    /// Parsing fails with `ParseError::NodeTooLarge` before anything is allocated for a larger node section.
+    /// The size of a compressed node section is read from its header, so this guards against a small corrupt data.arc causing a huge allocation.
+    pub fn max_node_size(mut self, max_size: u64) -> ParseOptions {
+        self.max_node_size = max_size;
+        self
+    }
+
+    /// Decompress file data with `decompressor`, defaults to `ZstdDecompressor`.
+    pub fn decompressor(mut self, decompressor: Box<dyn Decompressor>) -> ParseOptions {
+        self.decompressor = Some(decompressor);
+        self
+    }
+
+    /// Decode the entries of each section when they are first accessed, defaults to false.
+    ///
+    /// The node section is still read and checked when the `DataArc` is created, but e.g. the tree entries are only decoded
+    /// on the first call to `DataArc::tree_entries` or a method that needs them.
+    /// This makes opening a data.arc faster when only some of its sections are used.
+    pub fn lazy(mut self, lazy: bool) -> ParseOptions {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Fail with `ParseError::InvalidIndices` when `DataArc::validate_indices` finds an index out of range, defaults to false.
+    ///
+    /// This is not checked by `DataArc::new_lenient`, call `DataArc::validate_indices` on the parsed `DataArc` instead.
+    pub fn validate_indices(mut self, validate: bool) -> ParseOptions {
+        self.validate_indices = validate;
+        self
+    }
+
+    /// Fail with `ParseError::Misaligned` when `DataArc::check_alignment` finds a misaligned section, defaults to false.
+    ///
+    /// This is not checked by `DataArc::new_lenient`, call `DataArc::check_alignment` on the parsed `DataArc` instead.
+    pub fn check_alignment(mut self, check: bool) -> ParseOptions {
+        self.check_alignment = check;
+        self
+    }
+
+    /// Fail with `ParseError::SizeMismatch` when the known sections don't fill the node section, defaults to true.
+    ///
+    /// The node section may end with zero padding, only data that isn't zero after the `numbers` section fails, see `DataArc::trailing_node_bytes`.
+    /// This catches a count in the node header that is wrong for a new layout before every section after it is read from the wrong offset.
+    /// This is not checked by `DataArc::new_lenient`, which warns about the trailing data instead.
+    pub fn check_node_size(mut self, check: bool) -> ParseOptions {
+        self.check_node_size = check;
+        self
+    }
+
+    /// Fail with `ParseError::UnsupportedVersion` when `DataArc::version` doesn't recognise the layout of the node section, defaults to true.
+    ///
+    /// Turn this off to parse a data.arc with an unknown layout anyway, e.g. to investigate it with `DataArc::decode_section_at`.
+    /// Data that isn't zero after the known sections fails `ParseOptions::check_node_size` first, so turn that off too.
+    /// A node section with the pre-3.0.0 layout also fails `DataArc::new_lenient`, the other layouts are only warned about.
+    pub fn check_version(mut self, check: bool) -> ParseOptions {
+        self.check_version = check;
+        self
+    }
+
+    /// Memory map the file instead of reading from it, defaults to false.
+    ///
+    /// This allows `DataArc::mmap_slice` to borrow file data without copying it.
+    /// The file must not be modified by this or any other process while the `DataArc` exists.
+    #[cfg(feature = "mmap")]
+    pub fn mmap(mut self, mmap: bool) -> ParseOptions {
+        self.mmap = mmap;
+        self
+    }
+}
@@ -1,8 +1,14 @@
+// Not every field of the on-disk structures is used yet, they are kept to document the format.
+#![allow(dead_code)]
+
 use byteorder::{LittleEndian, ByteOrder};
 use scroll_derive::Pread;
 
+/// The header of the data.arc, following the magic number. The offsets are relative to the start of the data.arc.
 #[derive(Debug, Pread)]
-pub(crate) struct ArcHeader {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ArcHeader {
+    /// Start of the music file data, the sizes and offsets of the music files are in the `file_pairs` section of the node section
     pub music_file_section_offset: u64,
     pub file_section_offset: u64,
     pub music_section_offset: u64,
@@ -11,17 +17,26 @@ pub(crate) struct ArcHeader {
 }
 pub(crate) const ARC_HEADER_SIZE: usize = 0x28;
 
+/// The start of a compressed node section.
+///
+/// The zstd frame does not have to start right after this header, the layout is:
+/// *   `node_section_offset`: this header
+/// *   `node_section_offset + data_start`: the zstd frame, `zstd_comp_size` bytes long, which decompresses to `decomp_size` bytes
 #[derive(Debug, Pread)]
 pub(crate) struct CompressedNodeHeader {
+    /// Offset to the zstd frame, relative to the start of this header
     pub data_start: u32,
     pub decomp_size: u32,
+    /// Not used, the zstd frame is read with `zstd_comp_size`
     pub comp_size: u32,
     pub zstd_comp_size: u32,
 }
 pub(crate) const COMPRESSED_NODE_HEADER_SIZE: usize = 0x10;
 
+/// The start of the uncompressed node section, giving the number of entries in each of its sections.
 #[derive(Debug, Pread)]
-pub(crate) struct NodeHeader {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NodeHeader {
     pub file_size: u32,
     pub folder_count: u32,
     pub file_count1: u32,
@@ -30,6 +45,10 @@ pub(crate) struct NodeHeader {
     pub sub_files1_count: u32,
     pub file_lookup_count: u32,
     pub hash_folder_count: u32,
+    /// Not used to size any section.
+    /// Every section between the NodeHeader and the `numbers` section is sized by the other counts,
+    /// so if this counts a table it is either one of those (e.g. the 0x28 byte `trees` entries)
+    /// or a table stored after `numbers`. This has not been verified against a data.arc.
     pub file_information_count: u32,
 
     pub file_count2: u32,
@@ -37,6 +56,9 @@ pub(crate) struct NodeHeader {
     pub unk1: u32,
     pub unk2: u32,
 
+    /// Number of entries in the `another_hash_table` section.
+    /// Being a u8 caps the section at 255 entries, so this may really be wider than a u8 in some versions of the data.arc.
+    /// The sizing has not been verified against a data.arc, see `DataArc::another_hash_table`.
     pub another_hash_table_size: u8,
     pub unk3: u8,
     pub unk4: u16,
@@ -48,8 +70,83 @@ pub(crate) struct NodeHeader {
 }
 pub(crate) const NODE_HEADER_SIZE: usize = 0x44;
 
-#[derive(Debug)]
-pub(crate) struct EntryTriplet {
+/// Where each section sized by the counts of a `NodeHeader` starts, relative to the end of the node header,
+/// returned by `NodeHeader::section_offsets`.
+///
+/// The `bulkfile_category_info` section starts at 0 and `file_lookup_buckets` starts where the last of them ends.
+pub(crate) struct SectionOffsets {
+    pub bulkfile_hash_lookup: usize,
+    pub bulkfiles_by_name: usize,
+    pub bulkfile_lookup_to_fileidx: usize,
+    pub file_pairs: usize,
+    pub another_hash_table: usize,
+    pub big_hashes: usize,
+    pub big_files: usize,
+    pub folder_hash_lookup: usize,
+    pub trees: usize,
+    pub sub_files1: usize,
+    pub sub_files2: usize,
+    pub folder_to_big_hash: usize,
+    pub file_lookup_buckets: usize,
+}
+
+/// The end of the section at `start` holding `count` entries of `entry_size` bytes.
+///
+/// Saturates instead of overflowing, so a section whose end would overflow is found to run past the end of the node section.
+pub(crate) fn section_end(start: usize, entry_size: usize, count: u32) -> usize {
+    start.saturating_add(entry_size.saturating_mul(count as usize))
+}
+
+impl NodeHeader {
+    /// The offset of each section, found from the counts in the header.
+    ///
+    /// The header tells us how many entries are in each section.
+    /// From this we know the end of each section and thus the start of the next section.
+    /// The sections after `file_lookup_buckets` are sized by its first bucket, so they can't be found from the header alone.
+    pub(crate) fn section_offsets(&self) -> SectionOffsets {
+        let bulkfile_hash_lookup = section_end(0, ENTRY_TRIPLET_SIZE, self.movie_count);
+        let bulkfiles_by_name = section_end(bulkfile_hash_lookup, ENTRY_PAIR_SIZE, self.part1_count);
+        let bulkfile_lookup_to_fileidx = section_end(bulkfiles_by_name, ENTRY_TRIPLET_SIZE, self.part1_count);
+        let file_pairs = section_end(bulkfile_lookup_to_fileidx, 4, self.part2_count);
+        let another_hash_table = section_end(file_pairs, FILE_PAIR_SIZE, self.music_file_count);
+        let big_hashes = section_end(another_hash_table, ENTRY_TRIPLET_SIZE, self.another_hash_table_size as u32);
+        let big_files = section_end(big_hashes, BIG_HASH_ENTRY_SIZE, self.folder_count);
+        let folder_hash_lookup = section_end(big_files, BIG_FILE_ENTRY_SIZE, self.file_count1.saturating_add(self.file_count2));
+        let trees = section_end(folder_hash_lookup, ENTRY_PAIR_SIZE, self.hash_folder_count);
+        let sub_files1 = section_end(trees, TREE_ENTRY_SIZE, self.tree_count);
+        let sub_files2 = section_end(sub_files1, FILE_ENTRY_SIZE, self.sub_files1_count);
+        let folder_to_big_hash = section_end(sub_files2, FILE_ENTRY_SIZE, self.sub_files2_count);
+        let file_lookup_buckets = section_end(folder_to_big_hash, ENTRY_PAIR_SIZE, self.folder_count);
+        SectionOffsets {
+            bulkfile_hash_lookup, bulkfiles_by_name, bulkfile_lookup_to_fileidx, file_pairs, another_hash_table, big_hashes, big_files,
+            folder_hash_lookup, trees, sub_files1, sub_files2, folder_to_big_hash, file_lookup_buckets,
+        }
+    }
+
+    /// Every count of entries in a section, with the name of its field.
+    pub(crate) fn counts(&self) -> [(&'static str, u32); 12] {
+        [
+            ("movie_count", self.movie_count),
+            ("part1_count", self.part1_count),
+            ("part2_count", self.part2_count),
+            ("music_file_count", self.music_file_count),
+            ("folder_count", self.folder_count),
+            ("file_count1", self.file_count1),
+            ("file_count2", self.file_count2),
+            ("hash_folder_count", self.hash_folder_count),
+            ("tree_count", self.tree_count),
+            ("sub_files1_count", self.sub_files1_count),
+            ("sub_files2_count", self.sub_files2_count),
+            ("file_lookup_count", self.file_lookup_count),
+        ]
+    }
+}
+
+/// A hash40 with two values whose meaning depends on the section it is in.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntryTriplet {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hash"))]
     pub hash: u64, // 0x28 bits
     pub meta: u32, // 0x18 bits
     pub meta2: u32,
@@ -63,8 +160,11 @@ pub(crate) fn read_triplet(data: &[u8]) -> EntryTriplet {
     EntryTriplet { hash, meta, meta2 }
 }
 
+/// A hash40 paired with a value whose meaning depends on the section it is in.
 #[derive(Debug)]
-pub(crate) struct EntryPair {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntryPair {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hash"))]
     pub hash: u64, // 0x28 bits
     pub meta: u32, // 0x18 bits
 }
@@ -76,8 +176,12 @@ pub(crate) fn read_pair(data: &[u8]) -> EntryPair {
     EntryPair { hash, meta }
 }
 
+/// Describes a folder by the hash40 of its path, name and parent folder, returned by `DataArc::big_hashes`.
+///
+/// The `meta` of `path` is the index of the `BigFileEntry` holding the files of the folder.
 #[derive(Debug)]
-pub(crate) struct BigHashEntry {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BigHashEntry {
     pub path: EntryPair,
     pub folder: EntryPair,
     pub parent: EntryPair,
@@ -112,8 +216,12 @@ pub(crate) fn read_big_hash_entry(data: &[u8]) -> BigHashEntry {
     }
 }
 
+/// Describes a file by the hash40 of its path, extension and folder.
+///
+/// Use `DataArc::file_entry_for` to find the `FileEntry` describing where its data is stored.
 #[derive(Debug)]
-pub(crate) struct TreeEntry {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TreeEntry {
     pub path: EntryPair,
     pub ext: EntryPair,
     pub folder: EntryPair,
@@ -135,60 +243,240 @@ pub(crate) fn read_tree_entry(data: &[u8]) -> TreeEntry {
 }
 
 const TREE_SUBOFFSET_MASK: u32 = 0b11;
+const TREE_REDIRECT: u32 = 0x200000;
 impl TreeEntry {
     pub fn redirect(&self) -> bool {
-        self.flags & 0x200000 != 0
+        self.flags & TREE_REDIRECT != 0
     }
     pub fn suboffset_index(&self) -> bool {
         self.flags & TREE_SUBOFFSET_MASK == 0
     }
+
+    /// Split `flags` into the bits that are understood and those that aren't.
+    pub fn parsed_flags(&self) -> TreeFlags {
+        TreeFlags {
+            is_redirect: self.flags & TREE_REDIRECT != 0,
+            suboffset: self.flags & TREE_SUBOFFSET_MASK,
+            unknown: self.flags & !(TREE_REDIRECT | TREE_SUBOFFSET_MASK),
+        }
+    }
 }
 
-#[derive(Debug, Pread)]
-pub(crate) struct FilePair {
+/// The `flags` of a `TreeEntry`, returned by `TreeEntry::parsed_flags`.
+///
+/// The known bits are:
+///
+/// | Bits       | Field         |
+/// |------------|---------------|
+/// | `0x3`      | `suboffset`   |
+/// | `0x200000` | `is_redirect` |
+///
+/// Bits marking directories or localized files have not been identified yet, so any such bits are kept in `unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TreeFlags {
+    /// The file is a redirect to another file, see `TreeEntry::redirect`
+    pub is_redirect: bool,
+    /// How the `FileEntry` is found, 0 when it is at `suboffset_index`, see `DataArc::file_entry_for`
+    pub suboffset: u32,
+    /// Every other bit, in the same position as in `flags`
+    pub unknown: u32,
+}
+
+/// The size and offset of a music file, returned by `DataArc::music_files`.
+#[derive(Debug, Clone, Copy, Pread)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilePair {
     pub size: u64,
     pub offset: u64,
 }
 pub(crate) const FILE_PAIR_SIZE: usize = 0x10;
 
-#[derive(Debug, Pread)]
-pub(crate) struct BigFileEntry {
+pub(crate) fn read_file_pair(data: &[u8]) -> FilePair {
+    FilePair {
+        size: LittleEndian::read_u64(&data[0x00..]),
+        offset: LittleEndian::read_u64(&data[0x08..]),
+    }
+}
+
+/// A group of files stored together, returned by `DataArc::big_files`.
+///
+/// Its members are the `files` file entries starting at `suboffset_index`, see `DataArc::read_big_file_member`.
+#[derive(Debug, Clone, Copy, Pread)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BigFileEntry {
+    /// Offset of the data of the members, relative to `file_section_offset` in the header
     pub offset: u64,
     pub decomp_size: u32,
     pub comp_size: u32,
+    /// Index of the `FileEntry` of the first member
     pub suboffset_index: u32,
+    /// Number of members
     pub files: u32,
     pub unk3: u32,
 }
 pub(crate) const BIG_FILE_ENTRY_SIZE: usize = 0x1c;
 
-#[derive(Debug, Pread)]
-pub(crate) struct FileEntry {
+/// Describes where the data of a file is stored and how it is compressed.
+///
+/// Use `DataArc::is_shared` and `DataArc::resolve_shared` to find whether its data is shared with another file.
+#[derive(Debug, Clone, Copy, Pread)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FileEntry {
+    /// Offset of the data in units of 4 bytes, relative to the offset of the `BigFileEntry` it belongs to
     pub offset: u32,
+    /// Size of the data as stored in the data.arc
     pub comp_size: u32,
+    /// Size of the data once decompressed
     pub decomp_size: u32,
+    /// The bits `0x07000000` give how the data is compressed.
+    /// What the other bits mean is not known, including whether any of them mark the region or language of a file,
+    /// so the regions a data.arc supports can't be found yet.
     pub flags: u32,
 }
+
+pub(crate) fn read_file_entry(data: &[u8]) -> FileEntry {
+    FileEntry {
+        offset: LittleEndian::read_u32(&data[0x00..]),
+        comp_size: LittleEndian::read_u32(&data[0x04..]),
+        decomp_size: LittleEndian::read_u32(&data[0x08..]),
+        flags: LittleEndian::read_u32(&data[0x0c..]),
+    }
+}
 pub(crate) const FILE_ENTRY_SIZE: usize = 0x10;
 
 impl FileEntry {
-    pub fn suboffset_redir(&self) -> bool {
-        unimplemented!()
-    }
-    pub fn suboffset_tree_index(&self) -> usize {
-        unimplemented!()
-    }
     pub fn suboffset_decompressed(&self) -> bool {
         self.flags & 0x07000000 == 0
     }
     pub fn suboffset_compressed_zstd(&self) -> bool {
         self.flags & 0x07000000 == 0x03000000
     }
+    /// How the data is compressed, decoded from the bits `0x07000000` of `flags`.
+    pub fn compression(&self) -> Compression {
+        match self.flags & 0x07000000 {
+            0 => Compression::Stored,
+            0x03000000 => Compression::Zstd,
+            bits => Compression::Unknown(bits),
+        }
+    }
 }
 
-#[derive(Debug, Pread)]
+/// How the data of a file is compressed, returned by `FileEntry::compression`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Compression {
+    /// Stored uncompressed
+    Stored,
+    Zstd,
+    /// A compression that can't be read, holding the bits `0x07000000` of the flags
+    Unknown(u32),
+}
+
+#[derive(Debug, Default, Pread)]
 pub(crate) struct HashBucket {
     pub index: u32,
     pub num_entries: u32,
 }
 pub(crate) const HASH_BUCKET_SIZE: usize = 0x08;
+
+/// An entry of a section of the node section that can be decoded on its own, see `DataArc::decode_section_at`.
+pub trait SectionEntry: Sized {
+    /// Size of an entry as stored
+    const SIZE: usize;
+
+    /// Decode an entry from the start of `data`, which is at least `SIZE` bytes.
+    fn decode(data: &[u8]) -> Self;
+}
+
+impl SectionEntry for EntryPair {
+    const SIZE: usize = ENTRY_PAIR_SIZE;
+    fn decode(data: &[u8]) -> EntryPair {
+        read_pair(data)
+    }
+}
+
+impl SectionEntry for EntryTriplet {
+    const SIZE: usize = ENTRY_TRIPLET_SIZE;
+    fn decode(data: &[u8]) -> EntryTriplet {
+        read_triplet(data)
+    }
+}
+
+impl SectionEntry for FilePair {
+    const SIZE: usize = FILE_PAIR_SIZE;
+    fn decode(data: &[u8]) -> FilePair {
+        read_file_pair(data)
+    }
+}
+
+impl SectionEntry for BigFileEntry {
+    const SIZE: usize = BIG_FILE_ENTRY_SIZE;
+    fn decode(data: &[u8]) -> BigFileEntry {
+        BigFileEntry {
+            offset: LittleEndian::read_u64(&data[0x00..]),
+            decomp_size: LittleEndian::read_u32(&data[0x08..]),
+            comp_size: LittleEndian::read_u32(&data[0x0c..]),
+            suboffset_index: LittleEndian::read_u32(&data[0x10..]),
+            files: LittleEndian::read_u32(&data[0x14..]),
+            unk3: LittleEndian::read_u32(&data[0x18..]),
+        }
+    }
+}
+
+impl SectionEntry for BigHashEntry {
+    const SIZE: usize = BIG_HASH_ENTRY_SIZE;
+    fn decode(data: &[u8]) -> BigHashEntry {
+        read_big_hash_entry(data)
+    }
+}
+
+impl SectionEntry for TreeEntry {
+    const SIZE: usize = TREE_ENTRY_SIZE;
+    fn decode(data: &[u8]) -> TreeEntry {
+        read_tree_entry(data)
+    }
+}
+
+impl SectionEntry for FileEntry {
+    const SIZE: usize = FILE_ENTRY_SIZE;
+    fn decode(data: &[u8]) -> FileEntry {
+        read_file_entry(data)
+    }
+}
+
+/// Serialize a hash40 as a hex string, e.g. `0x1e8b7be43`, the same as it is formatted with `{:x?}`.
+#[cfg(feature = "serde")]
+fn serialize_hash<S: serde::Serializer>(hash: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:#x}", hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree_with_flags(flags: u32) -> TreeEntry {
+        let mut data = [0; TREE_ENTRY_SIZE];
+        data[0x24..].copy_from_slice(&flags.to_le_bytes());
+        read_tree_entry(&data)
+    }
+
+    #[test]
+    fn parsed_flags() {
+        let flags = |flags| tree_with_flags(flags).parsed_flags();
+        assert_eq!(flags(0), TreeFlags { is_redirect: false, suboffset: 0, unknown: 0 });
+        assert_eq!(flags(0x200000), TreeFlags { is_redirect: true, suboffset: 0, unknown: 0 });
+        assert_eq!(flags(0x2), TreeFlags { is_redirect: false, suboffset: 0x2, unknown: 0 });
+        assert_eq!(flags(0x200003), TreeFlags { is_redirect: true, suboffset: 0x3, unknown: 0 });
+        assert_eq!(flags(0x8000_0010), TreeFlags { is_redirect: false, suboffset: 0, unknown: 0x8000_0010 });
+    }
+
+    #[test]
+    fn flags_agree_with_parsed_flags() {
+        for &flags in &[0, 0x1, 0x200000, 0x200001, 0x400000] {
+            let tree = tree_with_flags(flags);
+            assert_eq!(tree.redirect(), tree.parsed_flags().is_redirect);
+            assert_eq!(tree.suboffset_index(), tree.parsed_flags().suboffset == 0);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use byteorder::{LittleEndian, ByteOrder};
+use failure::{Error, format_err};
+
+use crate::{DataArc, GetFileError};
+
+/// The largest zstd frame header, made of the magic number, descriptor, window descriptor, dictionary id and content size
+const MAX_FRAME_HEADER_SIZE: usize = 4 + 1 + 1 + 4 + 8;
+const ZSTD_MAGIC: u32 = 0xFD2FB528;
+
+/// The zstd frame header of a file, returned by `DataArc::probe_compression`.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionProbe {
+    /// Bytes of memory the decoder needs to keep to decompress the frame.
+    ///
+    /// Streaming zstd decoders refuse windows larger than 128 MB by default.
+    /// `DataArc::get_file` decompresses the entire file in one go so is not limited by this.
+    pub window_size: u64,
+    /// The id of the dictionary the frame was compressed with, 0 when no dictionary was used
+    pub dictionary_id: u32,
+    /// Size of the file once decompressed, when it is stored in the frame
+    pub content_size: Option<u64>,
+    /// If the frame can be decompressed by this crate, it does not support zstd dictionaries so `dictionary_id` must be 0
+    pub decodable: bool,
+}
+
+impl DataArc {
+    /// Inspect the zstd frame header of the first compressed file in the `trees` section.
+    ///
+    /// This reports the decoder settings the data.arc needs up front, rather than having extraction fail later.
+    /// Returns `GetFileError::FileNotFound` when no file is compressed with zstd.
+    pub fn probe_compression(&mut self) -> Result<CompressionProbe, GetFileError> {
+        let location = self.tree_entries().iter()
+            .filter_map(|tree| self.locate_tree(tree).ok())
+            .find(|location| location.sub_file.suboffset_compressed_zstd())
+            .ok_or(GetFileError::FileNotFound)?;
+        let size = (location.sub_file.comp_size as usize).min(MAX_FRAME_HEADER_SIZE);
+        let header = self.read_range(location.offset, size as u64)?;
+        Ok(read_frame_header(&header)?)
+    }
+}
+
+/// Decode the start of a zstd frame, as described by the zstd format specification (RFC 8878).
+fn read_frame_header(data: &[u8]) -> Result<CompressionProbe, Error> {
+    let truncated = || format_err!("Failed to probe compression: The zstd frame header is truncated");
+    if data.len() < 5 {
+        return Err(truncated());
+    }
+    if LittleEndian::read_u32(data) != ZSTD_MAGIC {
+        return Err(format_err!("Failed to probe compression: The file does not start with a zstd frame"));
+    }
+
+    let descriptor = data[4];
+    let content_size_flag = descriptor >> 6;
+    let single_segment = descriptor & 0x20 != 0;
+    let dictionary_id_size = [0, 1, 2, 4][(descriptor & 0b11) as usize];
+    let content_size_size = match content_size_flag {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+
+    let mut offset = 5;
+    let window_descriptor = if single_segment {
+        None
+    } else {
+        offset += 1;
+        Some(*data.get(5).ok_or_else(truncated)?)
+    };
+
+    let dictionary_id = data.get(offset..offset + dictionary_id_size).ok_or_else(truncated)?;
+    let dictionary_id = dictionary_id.iter().rev().fold(0, |acc, &x| acc << 8 | x as u32);
+    offset += dictionary_id_size;
+
+    let content_size = data.get(offset..offset + content_size_size).ok_or_else(truncated)?;
+    let content_size = match content_size_size {
+        0 => None,
+        // A two byte content size is stored minus 256
+        2 => Some(LittleEndian::read_u16(content_size) as u64 + 256),
+        _ => Some(content_size.iter().rev().fold(0, |acc, &x| acc << 8 | x as u64)),
+    };
+
+    let window_size = match window_descriptor {
+        Some(window_descriptor) => {
+            let window_log = 10 + (window_descriptor >> 3) as u64;
+            let window_base = 1u64 << window_log;
+            window_base + (window_base / 8) * (window_descriptor & 0b111) as u64
+        }
+        // A single segment frame is decompressed in one go so its window is the entire content
+        None => content_size.unwrap_or(0),
+    };
+
+    let decodable = dictionary_id == 0;
+    Ok(CompressionProbe { window_size, dictionary_id, content_size, decodable })
+}
@@ -0,0 +1,42 @@
+use std::ops::RangeInclusive;
+
+use crate::DataArc;
+
+/// The expected range of counts in the node header of a version of the data.arc, checked with `DataArc::matches_profile`.
+///
+/// No profiles are provided for released versions of the game, as their counts have not been recorded yet.
+#[derive(Debug, Clone)]
+pub struct ArcProfile {
+    /// The name of the version, e.g. to show which version a data.arc matched
+    pub name: String,
+    /// The name of a field of the node header, e.g. `tree_count`, with the range it must be within
+    pub counts: Vec<(&'static str, RangeInclusive<u32>)>,
+}
+
+impl ArcProfile {
+    /// A profile that every data.arc matches, until counts are added to it.
+    pub fn new(name: &str) -> ArcProfile {
+        ArcProfile { name: name.to_string(), counts: vec!() }
+    }
+
+    /// Expect the field of the node header named `count_name` to be within `range`.
+    ///
+    /// The counts that can be checked are `movie_count`, `part1_count`, `part2_count`, `music_file_count`, `folder_count`,
+    /// `file_count1`, `file_count2`, `hash_folder_count`, `tree_count`, `sub_files1_count`, `sub_files2_count` and `file_lookup_count`.
+    pub fn count(mut self, count_name: &'static str, range: RangeInclusive<u32>) -> ArcProfile {
+        self.counts.push((count_name, range));
+        self
+    }
+}
+
+impl DataArc {
+    /// Check that every count of `profile` is within its range.
+    ///
+    /// A profile with a count that isn't one of those listed in `ArcProfile::count` never matches.
+    pub fn matches_profile(&self, profile: &ArcProfile) -> bool {
+        let counts = self.node_header.counts();
+        profile.counts.iter().all(|(count_name, range)| {
+            counts.iter().any(|(name, count)| name == count_name && range.contains(count))
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{DataArc, GetFileError};
+
+/// Bytes that a file starts with at an offset.
+type Magic = (usize, &'static [u8]);
+
+/// The extension of each known file type, with the bytes its files start with at each offset.
+///
+/// The SSBH formats share the `HBSS` magic and are told apart by the magic of the data that follows it.
+/// Formats identified by a footer instead, e.g. nutexb, can't be found from the start of the file.
+const SIGNATURES: &[(&str, &[Magic])] = &[
+    ("prc", &[(0, b"paracobn")]),
+    ("msbt", &[(0, b"MsgStdBn")]),
+    ("nus3audio", &[(0, b"NUS3"), (8, b"AUDIINDX")]),
+    ("nus3bank", &[(0, b"NUS3"), (8, b"BANKTOC ")]),
+    ("numdlb", &[(0, b"HBSS"), (0x10, b"LDOM")]),
+    ("numshb", &[(0, b"HBSS"), (0x10, b"HSEM")]),
+    ("nusktb", &[(0, b"HBSS"), (0x10, b"LEKS")]),
+    ("numatb", &[(0, b"HBSS"), (0x10, b"LTAM")]),
+    ("nuanmb", &[(0, b"HBSS"), (0x10, b"MINA")]),
+    ("nuhlpb", &[(0, b"HBSS"), (0x10, b"BPLH")]),
+    ("bntx", &[(0, b"BNTX")]),
+    ("png", &[(0, b"\x89PNG")]),
+];
+
+/// Enough bytes to hold every magic in `SIGNATURES`.
+const SNIFF_SIZE: usize = 0x14;
+
+impl DataArc {
+    /// Guess the extension of the file at `file_name` from the magic bytes at its start, e.g. for a file whose extension has no label.
+    ///
+    /// Only the first few bytes of the file are read, see `peek_file`.
+    /// Returns `None` when the file doesn't match any of a small table of common file types.
+    pub fn sniff_type(&mut self, file_name: &str) -> Result<Option<&'static str>, GetFileError> {
+        let data = self.peek_file(file_name, SNIFF_SIZE)?;
+        Ok(sniff(&data))
+    }
+}
+
+/// The extension of the first signature that `data` matches.
+fn sniff(data: &[u8]) -> Option<&'static str> {
+    SIGNATURES.iter()
+        .find(|(_, parts)| parts.iter().all(|&(offset, magic)| data.get(offset..offset + magic.len()) == Some(magic)))
+        .map(|&(ext, _)| ext)
+}
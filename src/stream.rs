@@ -0,0 +1,218 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use failure::format_err;
+use scroll::{Pread, LE};
+
+use crate::{DATA_ARC_MAGIC, DataArc, ParseError, ParseOptions, check_entry_count, read_arc_header};
+use crate::parse::*;
+
+/// Reads the entries of the `trees` section one at a time, returned by `DataArc::stream_tree_entries`.
+pub struct TreeEntryStream {
+    /// Positioned at the next entry of the uncompressed node section
+    reader: Box<dyn Read>,
+    remaining: u32,
+}
+
+impl Iterator for TreeEntryStream {
+    type Item = Result<TreeEntry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let mut buffer = [0; TREE_ENTRY_SIZE];
+        match self.reader.read_exact(&mut buffer) {
+            Ok(()) => Some(Ok(read_tree_entry(&buffer))),
+            Err(err) => {
+                // The rest of the entries can't be read either
+                self.remaining = 0;
+                Some(Err(err.into()))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+impl DataArc {
+    /// Read the tree entries of the data.arc in `reader` one at a time, without reading the rest of the metadata.
+    /// The entries are yielded in the order they are stored, the same as `DataArc::tree_entries`.
+    ///
+    /// Only one entry is held in memory at a time, trading the random access of `DataArc::tree_entries` for low memory use.
+    /// A compressed node section is decompressed as it is read rather than all at once.
+    pub fn stream_tree_entries<R: Read + Seek + 'static>(reader: R) -> Result<TreeEntryStream, ParseError> {
+        let (_, node_header, mut reader) = open_node_section(reader)?;
+        skip(&mut reader, node_header.section_offsets().trees, "trees")?;
+        Ok(TreeEntryStream { reader, remaining: node_header.tree_count })
+    }
+}
+
+/// Receives the entries of a data.arc as they are read by `parse_streaming`.
+///
+/// Every method does nothing by default, so only the entries of interest need to be handled.
+/// The methods are called in the order the entries are stored in the data.arc.
+pub trait ArcVisitor {
+    fn on_header(&mut self, _header: &ArcHeader) {}
+    fn on_node_header(&mut self, _node_header: &NodeHeader) {}
+    /// Called for each entry of the `file_pairs` section, see `DataArc::music_files`
+    fn on_file_pair(&mut self, _index: usize, _file_pair: &FilePair) {}
+    /// Called for each entry of the `big_files` section, see `DataArc::big_files`
+    fn on_big_file(&mut self, _index: usize, _big_file: &BigFileEntry) {}
+    /// Called for each entry of the `trees` section, see `DataArc::tree_entries`
+    fn on_tree_entry(&mut self, _index: usize, _tree: &TreeEntry) {}
+    /// Called for each entry of the `sub_files1` section followed by the `sub_files2` section, see `DataArc::file_by_index`
+    fn on_file_entry(&mut self, _index: usize, _file_entry: &FileEntry) {}
+}
+
+/// Read the metadata of the data.arc in `reader`, passing each entry to `visitor` as it is read.
+///
+/// Only one entry is held in memory at a time, so this uses constant memory no matter the size of the data.arc.
+/// A compressed node section is decompressed as it is read rather than all at once.
+/// The sections that `ArcVisitor` has no method for are skipped.
+pub fn parse_streaming<R: Read + Seek, V: ArcVisitor>(reader: R, visitor: &mut V) -> Result<(), ParseError> {
+    let (header, node_header, mut reader) = open_node_section(reader)?;
+    visitor.on_header(&header);
+    visitor.on_node_header(&node_header);
+
+    let offsets = node_header.section_offsets();
+    skip(&mut reader, offsets.file_pairs, "file_pairs")?;
+    let mut buffer = [0; FILE_PAIR_SIZE];
+    for i in 0..node_header.music_file_count as usize {
+        reader.read_exact(&mut buffer)?;
+        visitor.on_file_pair(i, &read_file_pair(&buffer));
+    }
+
+    skip(&mut reader, offsets.big_files - offsets.another_hash_table, "big_files")?;
+    let mut buffer = [0; BIG_FILE_ENTRY_SIZE];
+    for i in 0..(node_header.file_count1 as usize + node_header.file_count2 as usize) {
+        reader.read_exact(&mut buffer)?;
+        visitor.on_big_file(i, &buffer.pread_with(0, LE)?);
+    }
+
+    skip(&mut reader, offsets.trees - offsets.folder_hash_lookup, "trees")?;
+    let mut buffer = [0; TREE_ENTRY_SIZE];
+    for i in 0..node_header.tree_count as usize {
+        reader.read_exact(&mut buffer)?;
+        visitor.on_tree_entry(i, &read_tree_entry(&buffer));
+    }
+
+    let mut buffer = [0; FILE_ENTRY_SIZE];
+    for i in 0..(node_header.sub_files1_count as usize + node_header.sub_files2_count as usize) {
+        reader.read_exact(&mut buffer)?;
+        visitor.on_file_entry(i, &read_file_entry(&buffer));
+    }
+    Ok(())
+}
+
+/// Read the headers of the data.arc in `reader`, returning them with a reader positioned at the end of the `NodeHeader`
+/// in the uncompressed node section.
+///
+/// Fails with `ParseError::SectionOverrun` when a count in the `NodeHeader` is larger than the default `ParseOptions::max_entries_per_section`.
+fn open_node_section<'a, R: Read + Seek + 'a>(mut reader: R) -> Result<(ArcHeader, NodeHeader, Box<dyn Read + 'a>), ParseError> {
+    reader.seek(SeekFrom::Start(0))?;
+    let header = read_arc_header(&mut reader, DATA_ARC_MAGIC)?;
+
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(header.node_section_offset))?;
+    let mut buffer = vec!(0; COMPRESSED_NODE_HEADER_SIZE);
+    reader.read_exact(&mut buffer)?;
+    let compressed: CompressedNodeHeader = buffer.pread_with(0, LE)?;
+
+    // Told apart the same way as in `DataArc::internal_new`
+    let mut reader: Box<dyn Read + 'a> = if compressed.data_start == 0 {
+        return Err(ParseError::InvalidNodeSize { size: 0, remaining: file_len.saturating_sub(header.node_section_offset) });
+    } else if compressed.data_start < 0x100 {
+        reader.seek(SeekFrom::Start(header.node_section_offset + compressed.data_start as u64))?;
+        Box::new(zstd::stream::Decoder::new(reader.take(compressed.zstd_comp_size as u64))?)
+    } else {
+        reader.seek(SeekFrom::Start(header.node_section_offset))?;
+        Box::new(reader.take(compressed.data_start as u64))
+    };
+
+    let mut buffer = vec!(0; NODE_HEADER_SIZE);
+    reader.read_exact(&mut buffer)?;
+    let node_header: NodeHeader = buffer.pread_with(0, LE)?;
+    let max_entries = ParseOptions::new().max_entries_per_section;
+    for &(count_name, count) in &node_header.counts() {
+        check_entry_count(count_name, count, max_entries)?;
+    }
+    Ok((header, node_header, reader))
+}
+
+/// Skip `len` bytes of `reader`, which are the bytes before the section `next_section`.
+fn skip(reader: &mut dyn Read, len: usize, next_section: &str) -> Result<(), ParseError> {
+    let skipped = io::copy(&mut reader.take(len as u64), &mut io::sink())?;
+    if skipped != len as u64 {
+        return Err(format_err!("The {} section starts past the end of the node section", next_section).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::ArcBuilder;
+    use super::*;
+
+    fn build_arc() -> Vec<u8> {
+        let mut builder = ArcBuilder::new();
+        builder.add_file("a/first.bin", b"first", true);
+        builder.add_file("a/b/second.bin", b"second", false);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn stream_matches_tree_entries() {
+        let data = build_arc();
+        let data_arc = DataArc::parse_bytes(&data).unwrap();
+        let streamed: Vec<TreeEntry> = DataArc::stream_tree_entries(Cursor::new(data)).unwrap().map(Result::unwrap).collect();
+        assert_eq!(format!("{:?}", streamed), format!("{:?}", data_arc.tree_entries()));
+    }
+
+    #[test]
+    fn huge_count_is_section_overrun() {
+        let mut data = build_arc();
+        let node = LittleEndian::read_u64(&data[0x20..]) as usize;
+        LittleEndian::write_u32(&mut data[node + 0x8..], u32::MAX); // file_count1
+        let result = DataArc::stream_tree_entries(Cursor::new(data));
+        assert!(matches!(result.err(), Some(ParseError::SectionOverrun { count_name: "file_count1", .. })));
+    }
+
+    #[derive(Default)]
+    struct Collect {
+        big_files: Vec<String>,
+        trees: Vec<String>,
+        file_entries: Vec<String>,
+    }
+
+    impl ArcVisitor for Collect {
+        fn on_big_file(&mut self, _index: usize, big_file: &BigFileEntry) {
+            self.big_files.push(format!("{:?}", big_file));
+        }
+        fn on_tree_entry(&mut self, _index: usize, tree: &TreeEntry) {
+            self.trees.push(format!("{:?}", tree));
+        }
+        fn on_file_entry(&mut self, _index: usize, file_entry: &FileEntry) {
+            self.file_entries.push(format!("{:?}", file_entry));
+        }
+    }
+
+    #[test]
+    fn parse_streaming_matches_parse() {
+        let data = build_arc();
+        let data_arc = DataArc::parse_bytes(&data).unwrap();
+        let mut visitor = Collect::default();
+        parse_streaming(Cursor::new(data), &mut visitor).unwrap();
+        assert_eq!(visitor.big_files, data_arc.big_files().map(|x| format!("{:?}", x)).collect::<Vec<_>>());
+        assert_eq!(visitor.trees, data_arc.tree_entries().iter().map(|x| format!("{:?}", x)).collect::<Vec<_>>());
+        let file_entries: Vec<String> = (0..).map_while(|i| data_arc.file_by_index(i)).map(|x| format!("{:?}", x)).collect();
+        assert_eq!(visitor.file_entries, file_entries);
+    }
+}
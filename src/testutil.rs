@@ -0,0 +1,20 @@
+//! Utilities for building data.arc files to test against, enabled by the `test-util` feature.
+
+use crate::ArcBuilder;
+
+/// Build a data.arc containing a file for each `(path, contents)` in `entries`.
+///
+/// Each file is compressed with zstd and can be read back with `DataArc::get_file(path)`.
+/// The data.arc is laid out as described by `ArcBuilder`.
+///
+/// # Panics
+///
+/// Panics when `ArcBuilder::build` fails, i.e. when there is too much data for the fields describing it, such as a file of 4 GiB or more.
+/// Use `ArcBuilder` directly to handle the error instead.
+pub fn build_minimal_arc(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = ArcBuilder::new();
+    for (path, contents) in entries {
+        builder.add_file(path, contents, true);
+    }
+    builder.build().unwrap()
+}